@@ -0,0 +1,11 @@
+fn main() {
+    // babeltrace2-sys builds and links its own static libbabeltrace2, but the plugin has to use
+    // the library of the babeltrace2 CLI loading it. The libraries of this crate come before the
+    // ones of its dependencies on the linker command line, so the shared library defines the
+    // symbols and the static one isn't pulled in.
+    println!("cargo:rustc-link-lib=dylib=babeltrace2");
+    if let Ok(dir) = std::env::var("BABELTRACE2_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={dir}");
+    }
+    println!("cargo:rerun-if-env-changed=BABELTRACE2_LIB_DIR");
+}
@@ -0,0 +1,242 @@
+//! The babeltrace2 plugin `fiasco` with the source component class `tbuf` of
+//! [`l4re_tracestream::bt_plugin`], as a shared library for the `babeltrace2` CLI:
+//!
+//! ```text
+//! cargo build --release -p babeltrace-plugin-fiasco
+//! babeltrace2 --plugin-path target/release list-plugins
+//! babeltrace2 --plugin-path target/release \
+//!     --component source.fiasco.tbuf --params 'path="capture.bin",clock-frequency=2000000000'
+//! ```
+//!
+//! The plugin is a crate of its own, so its exported symbols never end up in the
+//! `l4re_tracestream` binary, which has the plugins it uses linked in statically. The plugin
+//! links the libbabeltrace2 of the system (see `build.rs`), objects of two copies of the library
+//! can't be mixed.
+//!
+//! [`source_plugin_descriptors!`](babeltrace2_sys::source_plugin_descriptors) hands the state of
+//! the component over as `init_method_data`, which only our own pipeline can pass. So the
+//! descriptors are written out here like the `BT_PLUGIN_*` macros of
+//! `babeltrace2/plugin/plugin-dev.h` do, with a component built from its parameters.
+
+use babeltrace2_sys::ffi;
+use l4re_tracestream::bt_plugin::source::{
+    self, finalize, initialize, iterator_finalize, iterator_initialize, iterator_next,
+};
+use std::os::raw::c_char;
+
+// the structs of plugin-dev.h, which bindgen only gives us with generated names for the unions
+
+#[repr(C, packed)]
+pub struct PluginDescriptor {
+    name: *const c_char,
+}
+
+#[repr(C)]
+union PluginAttributeValue {
+    text: *const c_char,
+    // struct __bt_plugin_descriptor_version, the largest member
+    _version: [u64; 3],
+}
+
+#[repr(C, packed)]
+pub struct PluginAttribute {
+    plugin: *const PluginDescriptor,
+    type_name: *const c_char,
+    kind: ffi::__bt_plugin_descriptor_attribute_type,
+    value: PluginAttributeValue,
+}
+
+#[repr(C, packed)]
+pub struct ComponentClassDescriptor {
+    plugin: *const PluginDescriptor,
+    name: *const c_char,
+    kind: ffi::bt_component_class_type,
+    // the `methods` union has the message iterator "next" method of sources at its start
+    next: source::IteratorNextMethod,
+}
+
+#[repr(C)]
+union ComponentClassAttributeValue {
+    text: *const c_char,
+    initialize: source::InitializeMethod,
+    finalize: source::FinalizeMethod,
+    iterator_initialize: source::IteratorInitializeMethod,
+    iterator_finalize: source::IteratorFinalizeMethod,
+}
+
+#[repr(C, packed)]
+pub struct ComponentClassAttribute {
+    class: *const ComponentClassDescriptor,
+    type_name: *const c_char,
+    kind: ffi::__bt_plugin_component_class_descriptor_attribute_type,
+    value: ComponentClassAttributeValue,
+}
+
+// the descriptors are immutable and only read by babeltrace
+#[repr(transparent)]
+struct Static<T>(T);
+
+unsafe impl<T> Sync for Static<T> {}
+
+static PLUGIN: Static<PluginDescriptor> = Static(PluginDescriptor {
+    name: c"fiasco".as_ptr(),
+});
+
+#[used]
+#[unsafe(link_section = "__bt_plugin_descriptors")]
+static PLUGIN_PTR: Static<*const PluginDescriptor> = Static(&raw const PLUGIN.0);
+
+macro_rules! plugin_attribute {
+    ($name:ident, $kind:ident, $type_name:literal, $text:literal) => {
+        #[used]
+        #[unsafe(link_section = "__bt_plugin_descriptor_attributes")]
+        static $name: Static<*const PluginAttribute> = Static(&PluginAttribute {
+            plugin: &raw const PLUGIN.0,
+            type_name: $type_name.as_ptr(),
+            kind: ffi::__bt_plugin_descriptor_attribute_type::$kind,
+            value: PluginAttributeValue {
+                text: $text.as_ptr(),
+            },
+        });
+    };
+}
+
+plugin_attribute!(
+    PLUGIN_DESCRIPTION,
+    BT_PLUGIN_DESCRIPTOR_ATTRIBUTE_TYPE_DESCRIPTION,
+    c"description",
+    c"Fiasco trace buffer decoder"
+);
+plugin_attribute!(
+    PLUGIN_LICENSE,
+    BT_PLUGIN_DESCRIPTOR_ATTRIBUTE_TYPE_LICENSE,
+    c"license",
+    c"MIT"
+);
+
+static TBUF: Static<ComponentClassDescriptor> = Static(ComponentClassDescriptor {
+    plugin: &raw const PLUGIN.0,
+    name: c"tbuf".as_ptr(),
+    kind: ffi::bt_component_class_type::BT_COMPONENT_CLASS_TYPE_SOURCE,
+    next: iterator_next,
+});
+
+#[used]
+#[unsafe(link_section = "__bt_plugin_component_class_descriptors")]
+static TBUF_PTR: Static<*const ComponentClassDescriptor> = Static(&raw const TBUF.0);
+
+macro_rules! class_attribute {
+    ($name:ident, $kind:ident, $type_name:literal, $value:expr) => {
+        #[used]
+        #[unsafe(link_section = "__bt_plugin_component_class_descriptor_attributes")]
+        static $name: Static<*const ComponentClassAttribute> = Static(&ComponentClassAttribute {
+            class: &raw const TBUF.0,
+            type_name: $type_name.as_ptr(),
+            kind: ffi::__bt_plugin_component_class_descriptor_attribute_type::$kind,
+            value: $value,
+        });
+    };
+}
+
+class_attribute!(
+    TBUF_DESCRIPTION,
+    BT_PLUGIN_COMPONENT_CLASS_DESCRIPTOR_ATTRIBUTE_TYPE_DESCRIPTION,
+    c"description",
+    ComponentClassAttributeValue {
+        text: c"Decode the events of a Fiasco tbuf capture file or TCP stream".as_ptr()
+    }
+);
+class_attribute!(
+    TBUF_HELP,
+    BT_PLUGIN_COMPONENT_CLASS_DESCRIPTOR_ATTRIBUTE_TYPE_HELP,
+    c"help",
+    ComponentClassAttributeValue {
+        text: source::HELP.as_ptr()
+    }
+);
+class_attribute!(
+    TBUF_INITIALIZE,
+    BT_PLUGIN_COMPONENT_CLASS_DESCRIPTOR_ATTRIBUTE_TYPE_INITIALIZE_METHOD,
+    c"initialize_method",
+    ComponentClassAttributeValue { initialize }
+);
+class_attribute!(
+    TBUF_FINALIZE,
+    BT_PLUGIN_COMPONENT_CLASS_DESCRIPTOR_ATTRIBUTE_TYPE_FINALIZE_METHOD,
+    c"finalize_method",
+    ComponentClassAttributeValue { finalize }
+);
+class_attribute!(
+    TBUF_ITERATOR_INITIALIZE,
+    BT_PLUGIN_COMPONENT_CLASS_DESCRIPTOR_ATTRIBUTE_TYPE_MSG_ITER_INITIALIZE_METHOD,
+    c"msg_iter_initialize_method",
+    ComponentClassAttributeValue {
+        iterator_initialize
+    }
+);
+class_attribute!(
+    TBUF_ITERATOR_FINALIZE,
+    BT_PLUGIN_COMPONENT_CLASS_DESCRIPTOR_ATTRIBUTE_TYPE_MSG_ITER_FINALIZE_METHOD,
+    c"msg_iter_finalize_method",
+    ComponentClassAttributeValue { iterator_finalize }
+);
+
+// babeltrace finds the descriptors of a shared object through these functions (the
+// `BT_PLUGIN_MODULE()` of plugin-dev.h), the linker provides the section bounds
+unsafe extern "C" {
+    static __start___bt_plugin_descriptors: *const PluginDescriptor;
+    static __stop___bt_plugin_descriptors: *const PluginDescriptor;
+    static __start___bt_plugin_descriptor_attributes: *const PluginAttribute;
+    static __stop___bt_plugin_descriptor_attributes: *const PluginAttribute;
+    static __start___bt_plugin_component_class_descriptors: *const ComponentClassDescriptor;
+    static __stop___bt_plugin_component_class_descriptors: *const ComponentClassDescriptor;
+    static __start___bt_plugin_component_class_descriptor_attributes:
+        *const ComponentClassAttribute;
+    static __stop___bt_plugin_component_class_descriptor_attributes: *const ComponentClassAttribute;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_begin_section_plugin_descriptors() -> *const *const PluginDescriptor {
+    &raw const __start___bt_plugin_descriptors
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_end_section_plugin_descriptors() -> *const *const PluginDescriptor {
+    &raw const __stop___bt_plugin_descriptors
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_begin_section_plugin_descriptor_attributes()
+-> *const *const PluginAttribute {
+    &raw const __start___bt_plugin_descriptor_attributes
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_end_section_plugin_descriptor_attributes()
+-> *const *const PluginAttribute {
+    &raw const __stop___bt_plugin_descriptor_attributes
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_begin_section_component_class_descriptors()
+-> *const *const ComponentClassDescriptor {
+    &raw const __start___bt_plugin_component_class_descriptors
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_end_section_component_class_descriptors()
+-> *const *const ComponentClassDescriptor {
+    &raw const __stop___bt_plugin_component_class_descriptors
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_begin_section_component_class_descriptor_attributes()
+-> *const *const ComponentClassAttribute {
+    &raw const __start___bt_plugin_component_class_descriptor_attributes
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __bt_get_end_section_component_class_descriptor_attributes()
+-> *const *const ComponentClassAttribute {
+    &raw const __stop___bt_plugin_component_class_descriptor_attributes
+}
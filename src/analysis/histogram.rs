@@ -0,0 +1,65 @@
+use std::fmt;
+
+const BUCKETS: usize = 64;
+
+/// Histogram with power of two buckets, bucket `i` counts values in `[2^i, 2^(i+1))` (and zero
+/// for bucket 0)
+#[derive(Debug, Clone)]
+pub struct Log2Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for Log2Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Log2Histogram {
+    pub fn record(&mut self, value: u64) {
+        let bucket = (u64::BITS - value.leading_zeros()).saturating_sub(1) as usize;
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> u64 {
+        self.sum.checked_div(self.count).unwrap_or(0)
+    }
+
+    pub fn min(&self) -> u64 {
+        if self.count == 0 { 0 } else { self.min }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+}
+
+/// One line per non-empty bucket, `[lower, upper) count`
+impl fmt::Display for Log2Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, count) in self.buckets.iter().enumerate().filter(|(_, c)| **c != 0) {
+            let lower = if i == 0 { 0 } else { 1u64 << i };
+            let upper = 1u128 << (i + 1);
+            writeln!(f, "  [{lower}, {upper}) {count}")?;
+        }
+        Ok(())
+    }
+}
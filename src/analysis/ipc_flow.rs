@@ -0,0 +1,89 @@
+use crate::converter::kernel_object::KobjAddr;
+use std::collections::{HashMap, VecDeque};
+
+/// Messages which weren't received after this many seconds are dropped, e.g. when the trace
+/// misses the `IPCRES` of the receiver
+pub const MAX_AGE_SECONDS: u64 = 60;
+/// Most messages queued for a single receiver, the oldest are dropped beyond it
+pub const MAX_QUEUED: usize = 256;
+
+#[derive(Debug, Clone, Copy)]
+struct IncomingMessage {
+    flow_id: u64,
+    sender: KobjAddr,
+    expects_reply: bool,
+    tsc: u64,
+    // first two message registers, as seen by both ends
+    words: [u64; 2],
+}
+
+/// Correlates the send phase of an IPC with the receive phase of the receiving thread, so both
+/// ends can be tagged with the same flow id (the number of the sending `IPC` event).
+///
+/// A receive is matched with the oldest message to the thread carrying the same message words,
+/// or the oldest message at all if none does. Messages expire after [`MAX_AGE_SECONDS`], so one
+/// missed receive doesn't shift the flows of the receiver for the rest of the trace.
+#[derive(Debug)]
+pub struct IpcFlows {
+    max_age: u64,
+    incoming: HashMap<KobjAddr, VecDeque<IncomingMessage>>,
+    // callers waiting for the reply of a thread, by the thread which will reply
+    reply_to: HashMap<KobjAddr, KobjAddr>,
+}
+
+impl IpcFlows {
+    pub fn new(clock_frequency: u64) -> Self {
+        Self {
+            max_age: MAX_AGE_SECONDS * clock_frequency,
+            incoming: HashMap::new(),
+            reply_to: HashMap::new(),
+        }
+    }
+
+    /// Records a message from `sender` to `receiver`
+    pub fn send(
+        &mut self,
+        flow_id: u64,
+        sender: KobjAddr,
+        receiver: KobjAddr,
+        expects_reply: bool,
+        tsc: u64,
+        words: [u64; 2],
+    ) {
+        let queue = self.incoming.entry(receiver).or_default();
+        if queue.len() >= MAX_QUEUED {
+            queue.pop_front();
+        }
+        queue.push_back(IncomingMessage {
+            flow_id,
+            sender,
+            expects_reply,
+            tsc,
+            words,
+        });
+    }
+
+    /// The thread a reply of `replier` goes to
+    pub fn reply_target(&mut self, replier: KobjAddr) -> Option<KobjAddr> {
+        self.reply_to.remove(&replier)
+    }
+
+    /// The message `receiver` got at `tsc` with the given message words, as flow id and sender
+    pub fn receive(
+        &mut self,
+        receiver: KobjAddr,
+        tsc: u64,
+        words: [u64; 2],
+    ) -> Option<(u64, KobjAddr)> {
+        let max_age = self.max_age;
+        let queue = self.incoming.get_mut(&receiver)?;
+        queue.retain(|m| tsc.saturating_sub(m.tsc) <= max_age);
+
+        let index = queue.iter().position(|m| m.words == words).unwrap_or(0);
+        let msg = queue.remove(index)?;
+        if msg.expects_reply {
+            self.reply_to.insert(receiver, msg.sender);
+        }
+        Some((msg.flow_id, msg.sender))
+    }
+}
@@ -0,0 +1,284 @@
+use super::ipc_latency::CompletedIpc;
+use crate::sink::{Detail, SinkEvent, TraceSink};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// size of a message word on the traced (64 bit) system
+const WORD_SIZE: u64 = 8;
+
+/// What the source nodes of the IPC graph are
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GraphNodes {
+    #[default]
+    Thread,
+    Task,
+}
+
+#[derive(Debug, Default)]
+struct EdgeStats {
+    callee: String,
+    calls: u64,
+    words: u64,
+    items: u64,
+    latencies_ns: Vec<u64>,
+}
+
+#[derive(Serialize)]
+struct JsonLatency {
+    count: usize,
+    p50: u64,
+    p90: u64,
+    p99: u64,
+    max: u64,
+}
+
+#[derive(Serialize)]
+struct JsonEdge<'a> {
+    src: &'a str,
+    dst: &'a str,
+    callee: &'a str,
+    calls: u64,
+    words: u64,
+    items: u64,
+    bytes: u64,
+    latency_ns: JsonLatency,
+}
+
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    nodes: &'a str,
+    edges: Vec<JsonEdge<'a>>,
+}
+
+/// Directed graph of the IPC communication, from callers (threads or tasks) to the gates or
+/// threads they send to, weighted with the number of messages, the transferred words and the
+/// round-trip latencies
+#[derive(Debug, Default)]
+pub struct IpcGraph {
+    nodes: GraphNodes,
+    edges: BTreeMap<(String, String), EdgeStats>,
+}
+
+impl IpcGraph {
+    pub fn new(nodes: GraphNodes) -> Self {
+        Self {
+            nodes,
+            edges: BTreeMap::new(),
+        }
+    }
+
+    fn source<'a>(&self, caller: &'a str, caller_task: &'a str) -> &'a str {
+        match self.nodes {
+            GraphNodes::Thread => caller,
+            GraphNodes::Task => caller_task,
+        }
+    }
+
+    /// Records a message sent by `caller` of the task `caller_task` to `gate`, with the number of
+    /// words and items of its message tag
+    pub fn record_message(
+        &mut self,
+        caller: &str,
+        caller_task: &str,
+        gate: &str,
+        callee: &str,
+        words: u64,
+        items: u64,
+    ) {
+        let src = self.source(caller, caller_task).to_string();
+        let edge = self.edges.entry((src, gate.to_string())).or_default();
+        edge.calls += 1;
+        edge.words += words;
+        edge.items += items;
+        if edge.callee.is_empty() {
+            edge.callee = callee.to_string();
+        }
+    }
+
+    pub fn record_completion(&mut self, ipc: &CompletedIpc) {
+        let src = self.source(&ipc.caller, &ipc.caller_task);
+        if let Some(edge) = self.edges.get_mut(&(src.to_string(), ipc.gate.clone())) {
+            edge.latencies_ns.push(ipc.latency_ns);
+        }
+    }
+
+    fn latency(edge: &EdgeStats) -> JsonLatency {
+        let mut sorted = edge.latencies_ns.clone();
+        sorted.sort_unstable();
+        let percentile = |p: usize| {
+            if sorted.is_empty() {
+                0
+            } else {
+                sorted[(sorted.len() - 1) * p / 100]
+            }
+        };
+
+        JsonLatency {
+            count: sorted.len(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: sorted.last().copied().unwrap_or(0),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let graph = JsonGraph {
+            nodes: match self.nodes {
+                GraphNodes::Thread => "thread",
+                GraphNodes::Task => "task",
+            },
+            edges: self
+                .edges
+                .iter()
+                .map(|((src, dst), e)| JsonEdge {
+                    src,
+                    dst,
+                    callee: &e.callee,
+                    calls: e.calls,
+                    words: e.words,
+                    items: e.items,
+                    bytes: e.words * WORD_SIZE,
+                    latency_ns: Self::latency(e),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&graph)
+    }
+
+    /// Graphviz representation, callers are boxes and gates ellipses
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph ipc {\n");
+        let mut sources: Vec<&str> = self.edges.keys().map(|(src, _)| src.as_str()).collect();
+        sources.dedup();
+        for src in sources {
+            let _ = writeln!(out, "  \"{}\" [shape=box];", dot_escape(src));
+        }
+
+        for ((src, dst), e) in &self.edges {
+            let latency = Self::latency(e);
+            let _ = writeln!(
+                out,
+                "  \"{}\" -> \"{}\" [label=\"{} calls\\n{} bytes\\np50 {} us\"];",
+                dot_escape(src),
+                dot_escape(dst),
+                e.calls,
+                e.words * WORD_SIZE,
+                latency.p50 / 1000
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl TraceSink for IpcGraph {
+    fn event(&mut self, event: &SinkEvent) {
+        for detail in &event.details {
+            match detail {
+                Detail::IpcMessage {
+                    gate,
+                    callee,
+                    words,
+                    items,
+                } => self.record_message(
+                    &event.thread.name,
+                    &event.thread.task,
+                    gate,
+                    callee,
+                    *words,
+                    *items,
+                ),
+                Detail::IpcCompleted(ipc) => self.record_completion(ipc),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::kernel_object::KernelObjectMap;
+    use crate::enrich::Enricher;
+    use crate::parser::EventParser;
+    use crate::sampling::Sampler;
+    use crate::symbols::Symbolizer;
+    use serde_json::Value;
+    use std::io::Cursor;
+
+    const CLOCK_FREQUENCY: u64 = 1_000_000_000;
+    const CLIENT: u64 = 0xffff_8000_0010_0000;
+    const SERVER: u64 = 0xffff_8000_0020_0000;
+    const GATE: u64 = 0xffff_8000_0040_0040;
+
+    // a raw event with the payload after its 2 bytes of padding
+    fn record(number: u64, type_: u8, tsc: u64, ctx: u64, payload: &[u64]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(number.to_le_bytes());
+        buf.extend(0u64.to_le_bytes()); // ip
+        buf.extend(tsc.to_le_bytes());
+        buf.extend(ctx.to_le_bytes());
+        buf.extend([0; 12]); // pmc1, pmc2, kclock
+        buf.extend([type_, 0, 0, 0]); // type, cpu, padding
+        for w in payload {
+            buf.extend(w.to_le_bytes());
+        }
+        buf.resize(128, 0);
+        buf
+    }
+
+    fn nam(number: u64, obj: u64, thread: u64, id: u64, name: &str) -> Vec<u8> {
+        let mut buf = record(number, 21, 0, CLIENT, &[obj, thread, id]);
+        buf[72..72 + name.len()].copy_from_slice(name.as_bytes());
+        buf
+    }
+
+    fn graph(nodes: GraphNodes) -> Value {
+        let mut ipc_res = record(4, 3, 3000, CLIENT, &[0x1_0000, 3, 4, 0, 0, 0, 3]);
+        ipc_res[32 + 12 + 2] = 1; // have_snd
+        let raw = [
+            nam(1, SERVER, 0, 0x1b, "server"),
+            nam(2, GATE, SERVER, 0x1d, "srv_gate"),
+            // call to the gate by a thread which has no name and whose task isn't known
+            record(3, 2, 1000, CLIENT, &[0x2_0000, 1, 2, 0, 0x1d, 0, 0, 0]),
+            ipc_res,
+        ]
+        .concat();
+
+        let mut enricher = Enricher::new(
+            KernelObjectMap::default(),
+            Symbolizer::load(None, &[]).unwrap(),
+            Default::default(),
+            Sampler::new(Vec::new(), CLOCK_FREQUENCY),
+            CLOCK_FREQUENCY,
+        );
+        let mut graph = IpcGraph::new(nodes);
+        let mut reader = Cursor::new(raw);
+        while let Some(event) = EventParser::next_event(&mut reader).unwrap() {
+            if let Some(event) = enricher.process(event) {
+                graph.event(&event);
+            }
+        }
+        serde_json::from_str(&graph.to_json().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn completion_of_unnamed_task() {
+        for nodes in [GraphNodes::Thread, GraphNodes::Task] {
+            let graph = graph(nodes);
+            let edges = graph["edges"].as_array().unwrap();
+            assert_eq!(edges.len(), 1);
+            assert_eq!(edges[0]["dst"], "srv_gate");
+            assert_eq!(edges[0]["calls"], 1);
+            assert_eq!(edges[0]["latency_ns"]["count"], 1, "{nodes:?}");
+            assert_eq!(edges[0]["latency_ns"]["max"], 2000);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use super::histogram::Log2Histogram;
+use super::ipc_flow::MAX_AGE_SECONDS;
+use super::ticks_to_ns;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// An IPC which waits for its `IPCRES`
+#[derive(Debug, Clone)]
+pub struct PendingIpc {
+    pub tsc: u64,
+    pub caller: String,
+    /// Task of the caller, as in [`ThreadInfo::task`](crate::sink::ThreadInfo::task)
+    pub caller_task: String,
+    pub gate: String,
+    pub callee: String,
+}
+
+/// An IPC matched with its result
+#[derive(Debug, Clone)]
+pub struct CompletedIpc {
+    pub caller: String,
+    pub caller_task: String,
+    pub gate: String,
+    pub callee: String,
+    /// Time from the `IPC` to the `IPCRES` event in clock ticks
+    pub latency: u64,
+    pub latency_ns: u64,
+}
+
+/// Pairs `IPC` events with their `IPCRES` (via `IpcResEvent::pair_event`, the number of the `IPC`
+/// event) and collects the round-trip latencies per caller and gate. IPCs without a result after
+/// [`MAX_AGE_SECONDS`] are given up on.
+#[derive(Debug)]
+pub struct IpcLatency {
+    clock_frequency: u64,
+    pending: HashMap<u64, PendingIpc>,
+    // given up IPCs and the time of the last check for them
+    expired: u64,
+    last_expiry: u64,
+    histograms: BTreeMap<(String, String), Log2Histogram>,
+}
+
+impl IpcLatency {
+    pub fn new(clock_frequency: u64) -> Self {
+        Self {
+            clock_frequency,
+            pending: HashMap::new(),
+            expired: 0,
+            last_expiry: 0,
+            histograms: BTreeMap::new(),
+        }
+    }
+
+    pub fn start(&mut self, event_number: u64, ipc: PendingIpc) {
+        let max_age = MAX_AGE_SECONDS * self.clock_frequency;
+        if ipc.tsc.saturating_sub(self.last_expiry) > max_age {
+            let before = self.pending.len();
+            self.pending
+                .retain(|_, p| ipc.tsc.saturating_sub(p.tsc) <= max_age);
+            self.expired += (before - self.pending.len()) as u64;
+            self.last_expiry = ipc.tsc;
+        }
+        self.pending.insert(event_number, ipc);
+    }
+
+    pub fn finish(&mut self, pair_event: u64, tsc: u64) -> Option<CompletedIpc> {
+        let ipc = self.pending.remove(&pair_event)?;
+        let latency = tsc.saturating_sub(ipc.tsc);
+        let latency_ns = ticks_to_ns(latency, self.clock_frequency);
+
+        self.histograms
+            .entry((ipc.caller.clone(), ipc.gate.clone()))
+            .or_default()
+            .record(latency_ns);
+
+        Some(CompletedIpc {
+            caller: ipc.caller,
+            caller_task: ipc.caller_task,
+            gate: ipc.gate,
+            callee: ipc.callee,
+            latency,
+            latency_ns,
+        })
+    }
+
+    /// Drops the IPC of a result which is left out, e.g. by the filter, so it isn't counted as
+    /// an IPC without result
+    pub fn forget(&mut self, pair_event: u64) {
+        self.pending.remove(&pair_event);
+    }
+
+    /// Latency histograms (in nanoseconds) per caller and gate
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "IPC LATENCY (ns), {} IPCS WITHOUT RESULT",
+            self.pending.len() as u64 + self.expired
+        );
+        for ((caller, gate), hist) in &self.histograms {
+            let _ = writeln!(
+                out,
+                "{caller} -> {gate}: count {} min {} mean {} max {}",
+                hist.count(),
+                hist.min(),
+                hist.mean(),
+                hist.max()
+            );
+            let _ = write!(out, "{hist}");
+        }
+        out
+    }
+}
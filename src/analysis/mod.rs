@@ -0,0 +1,13 @@
+pub mod histogram;
+pub mod ipc_flow;
+pub mod ipc_graph;
+pub mod ipc_latency;
+pub mod sched_stats;
+
+/// Converts clock ticks of the trace clock to nanoseconds
+pub fn ticks_to_ns(ticks: u64, clock_frequency: u64) -> u64 {
+    if clock_frequency == 0 {
+        return ticks;
+    }
+    (ticks as u128 * 1_000_000_000 / clock_frequency as u128) as u64
+}
@@ -0,0 +1,460 @@
+use super::ticks_to_ns;
+use crate::converter::kernel_object::{KernelObjectMap, UNKNOWN_TID};
+use crate::sink::{Detail, SinkEvent, ThreadInfo, TraceSink};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// the idle threads of all CPUs get debug id 0
+const IDLE_TID: i64 = 0;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// Print a table after the run
+    Table,
+    /// Write sched_threads.csv, sched_tasks.csv, sched_cpus.csv and sched_contexts.csv next to the
+    /// trace
+    Csv,
+    /// Write sched_stats.json next to the trace
+    Json,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Counters {
+    // clock ticks per CPU
+    cpu_time: BTreeMap<u8, u64>,
+    switches: u64,
+    preemptions: u64,
+    blocks: u64,
+    slices: u64,
+}
+
+impl Counters {
+    fn add(&mut self, other: &Counters) {
+        for (cpu, ticks) in &other.cpu_time {
+            *self.cpu_time.entry(*cpu).or_default() += ticks;
+        }
+        self.switches += other.switches;
+        self.preemptions += other.preemptions;
+        self.blocks += other.blocks;
+        self.slices += other.slices;
+    }
+}
+
+#[derive(Debug, Default)]
+struct ThreadStats {
+    name: String,
+    pid: i64,
+    task: String,
+    counters: Counters,
+}
+
+#[derive(Debug, Default)]
+struct CpuStats {
+    first: Option<u64>,
+    last: u64,
+    idle: u64,
+    // start of the current run slice
+    since: Option<u64>,
+}
+
+/// CPU time and scheduling statistics per thread, task and CPU, collected from all context
+/// switches, including the ones the filter or the sampler left out
+#[derive(Debug)]
+pub struct SchedStats {
+    clock_frequency: u64,
+    threads: BTreeMap<i64, ThreadStats>,
+    cpus: BTreeMap<u8, CpuStats>,
+}
+
+impl SchedStats {
+    pub fn new(clock_frequency: u64) -> Self {
+        Self {
+            clock_frequency,
+            threads: BTreeMap::new(),
+            cpus: BTreeMap::new(),
+        }
+    }
+
+    /// Records a switch from `prev` to `next` on `cpu`, `preempted` if `prev` did not block
+    pub fn record_switch(
+        &mut self,
+        cpu: u8,
+        tsc: u64,
+        prev: &ThreadInfo,
+        next: &ThreadInfo,
+        preempted: bool,
+    ) {
+        let cpu_stats = self.cpus.entry(cpu).or_default();
+        cpu_stats.first.get_or_insert(tsc);
+        cpu_stats.last = tsc;
+        let slice = cpu_stats
+            .since
+            .replace(tsc)
+            .map(|since| tsc.saturating_sub(since));
+
+        if prev.tid == IDLE_TID {
+            cpu_stats.idle += slice.unwrap_or(0);
+        } else {
+            let thread = self.thread(prev);
+            // the first slice of each CPU started before the trace, so it isn't counted at all
+            if let Some(slice) = slice {
+                *thread.counters.cpu_time.entry(cpu).or_default() += slice;
+                thread.counters.slices += 1;
+                if preempted {
+                    thread.counters.preemptions += 1;
+                } else {
+                    thread.counters.blocks += 1;
+                }
+            }
+        }
+
+        if next.tid != IDLE_TID {
+            self.thread(next).counters.switches += 1;
+        }
+    }
+
+    fn thread(&mut self, t: &ThreadInfo) -> &mut ThreadStats {
+        let stats = self.threads.entry(t.tid).or_default();
+        stats.name.clone_from(&t.name);
+        stats.task.clone_from(&t.task);
+        stats.pid = t.pid;
+        stats
+    }
+
+    /// The statistics in nanoseconds, with the scheduling contexts from the kernel object map
+    pub fn report(&self, map: &KernelObjectMap) -> SchedReport {
+        let ns = |ticks: u64| ticks_to_ns(ticks, self.clock_frequency);
+
+        let mut tasks: BTreeMap<i64, (&str, Counters)> = BTreeMap::new();
+        let threads = self
+            .threads
+            .iter()
+            .map(|(tid, t)| {
+                let task = tasks.entry(t.pid).or_default();
+                task.0 = &t.task;
+                task.1.add(&t.counters);
+                SchedRow::new(*tid, t.pid, &t.task, &t.name, &t.counters, ns)
+            })
+            .collect();
+        let tasks = tasks
+            .iter()
+            .map(|(pid, (name, c))| SchedRow::new(*pid, *pid, name, name, c, ns))
+            .collect();
+        let cpus = self
+            .cpus
+            .iter()
+            .map(|(cpu, c)| {
+                let observed = ns(c.last - c.first.unwrap_or(c.last));
+                let idle = ns(c.idle);
+                CpuRow {
+                    cpu: *cpu,
+                    observed_ns: observed,
+                    idle_ns: idle,
+                    idle_percent: if observed == 0 {
+                        0.0
+                    } else {
+                        idle as f64 * 100.0 / observed as f64
+                    },
+                }
+            })
+            .collect();
+        let mut sched_contexts: Vec<SchedContextRow> = map
+            .sched_contexts()
+            .map(|((owner, id), sc)| SchedContextRow {
+                tid: map.get(owner).map(|o| o.tid()).unwrap_or(UNKNOWN_TID),
+                name: map.label(*owner),
+                sc_id: *id,
+                prio: sc.prio,
+                quantum_us: sc.quantum,
+                loads: sc.loads,
+                preemptions: sc.preemptions,
+                exhaustions: sc.exhaustions,
+                min_left_us: sc.min_left,
+                prio_changes: sc.prio_changes,
+            })
+            .collect();
+        sched_contexts.sort_by_key(|sc| (sc.tid, sc.sc_id));
+
+        SchedReport {
+            threads,
+            tasks,
+            cpus,
+            sched_contexts,
+        }
+    }
+}
+
+impl TraceSink for SchedStats {
+    fn event(&mut self, event: &SinkEvent) {
+        self.left_out(event);
+    }
+
+    // the statistics cover all context switches, whether converted or not
+    fn left_out(&mut self, event: &SinkEvent) {
+        for detail in &event.details {
+            if let Detail::ContextSwitch {
+                prev,
+                next,
+                preempted,
+            } = detail
+            {
+                self.record_switch(event.cpu, event.tsc, prev, next, *preempted);
+            }
+        }
+    }
+}
+
+/// Statistics of a thread or (summed up over its threads) of a task
+#[derive(Debug, Serialize)]
+pub struct SchedRow {
+    pub tid: i64,
+    pub pid: i64,
+    pub task: String,
+    pub name: String,
+    pub cpu_time_ns: BTreeMap<u8, u64>,
+    pub total_ns: u64,
+    pub switches: u64,
+    pub preemptions: u64,
+    pub blocks: u64,
+    pub avg_slice_ns: u64,
+}
+
+impl SchedRow {
+    fn new(
+        tid: i64,
+        pid: i64,
+        task: &str,
+        name: &str,
+        c: &Counters,
+        ns: impl Fn(u64) -> u64,
+    ) -> Self {
+        let cpu_time_ns: BTreeMap<u8, u64> =
+            c.cpu_time.iter().map(|(cpu, t)| (*cpu, ns(*t))).collect();
+        let total_ns = cpu_time_ns.values().sum();
+
+        Self {
+            tid,
+            pid,
+            task: task.to_string(),
+            name: name.to_string(),
+            cpu_time_ns,
+            total_ns,
+            switches: c.switches,
+            preemptions: c.preemptions,
+            blocks: c.blocks,
+            avg_slice_ns: total_ns.checked_div(c.slices).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CpuRow {
+    pub cpu: u8,
+    pub observed_ns: u64,
+    pub idle_ns: u64,
+    pub idle_percent: f64,
+}
+
+/// Budget statistics of a scheduling context, from the `SCHED` events
+#[derive(Debug, Serialize)]
+pub struct SchedContextRow {
+    pub tid: i64,
+    pub name: String,
+    pub sc_id: u16,
+    pub prio: u16,
+    pub quantum_us: u64,
+    pub loads: u64,
+    pub preemptions: u64,
+    pub exhaustions: u64,
+    /// Smallest budget left when the context got preempted
+    pub min_left_us: Option<i64>,
+    pub prio_changes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchedReport {
+    pub threads: Vec<SchedRow>,
+    pub tasks: Vec<SchedRow>,
+    pub cpus: Vec<CpuRow>,
+    pub sched_contexts: Vec<SchedContextRow>,
+}
+
+impl SchedReport {
+    /// Human readable table of all threads, tasks and CPUs
+    pub fn table(&self) -> String {
+        let mut out = String::from("SCHEDULING STATISTICS\n");
+        for (title, rows) in [("THREAD", &self.threads), ("TASK", &self.tasks)] {
+            let _ = writeln!(
+                out,
+                "{:>8} {:>8} {:<24} {:>14} {:>8} {:>8} {:>8} {:>14}",
+                "TID",
+                "PID",
+                title,
+                "CPU TIME (ns)",
+                "SWITCHES",
+                "PREEMPT",
+                "BLOCK",
+                "AVG SLICE (ns)"
+            );
+            for row in rows {
+                let _ = writeln!(
+                    out,
+                    "{:>8} {:>8} {:<24} {:>14} {:>8} {:>8} {:>8} {:>14}",
+                    row.tid,
+                    row.pid,
+                    row.name,
+                    row.total_ns,
+                    row.switches,
+                    row.preemptions,
+                    row.blocks,
+                    row.avg_slice_ns
+                );
+            }
+        }
+        for cpu in &self.cpus {
+            let _ = writeln!(
+                out,
+                "CPU {}: observed {} ns, idle {} ns ({:.1}%)",
+                cpu.cpu, cpu.observed_ns, cpu.idle_ns, cpu.idle_percent
+            );
+        }
+        for sc in &self.sched_contexts {
+            let _ = writeln!(
+                out,
+                "SC {}/{} ({}): prio {} quantum {} us, {} loads, {} preemptions (min left {} us), {} expired, {} prio changes",
+                sc.tid,
+                sc.sc_id,
+                sc.name,
+                sc.prio,
+                sc.quantum_us,
+                sc.loads,
+                sc.preemptions,
+                sc.min_left_us
+                    .map(|l| l.to_string())
+                    .unwrap_or("-".to_string()),
+                sc.exhaustions,
+                sc.prio_changes
+            );
+        }
+        out
+    }
+
+    /// Writes the report to `dir` as JSON or CSV
+    pub fn write(&self, dir: &Path, format: StatsFormat) -> io::Result<()> {
+        match format {
+            StatsFormat::Table => Ok(()),
+            StatsFormat::Json => {
+                let mut out = BufWriter::new(File::create(dir.join("sched_stats.json"))?);
+                serde_json::to_writer_pretty(&mut out, self)?;
+                out.flush()
+            }
+            StatsFormat::Csv => {
+                let cpus: BTreeSet<u8> = self.cpus.iter().map(|c| c.cpu).collect();
+                write_rows(&dir.join("sched_threads.csv"), &self.threads, &cpus)?;
+                write_rows(&dir.join("sched_tasks.csv"), &self.tasks, &cpus)?;
+
+                let mut out = BufWriter::new(File::create(dir.join("sched_cpus.csv"))?);
+                writeln!(out, "cpu,observed_ns,idle_ns,idle_percent")?;
+                for c in &self.cpus {
+                    writeln!(
+                        out,
+                        "{},{},{},{:.3}",
+                        c.cpu, c.observed_ns, c.idle_ns, c.idle_percent
+                    )?;
+                }
+                out.flush()?;
+
+                let mut out = BufWriter::new(File::create(dir.join("sched_contexts.csv"))?);
+                writeln!(
+                    out,
+                    "tid,name,sc_id,prio,quantum_us,loads,preemptions,exhaustions,min_left_us,prio_changes"
+                )?;
+                for sc in &self.sched_contexts {
+                    writeln!(
+                        out,
+                        "{},\"{}\",{},{},{},{},{},{},{},{}",
+                        sc.tid,
+                        sc.name.replace('"', "\"\""),
+                        sc.sc_id,
+                        sc.prio,
+                        sc.quantum_us,
+                        sc.loads,
+                        sc.preemptions,
+                        sc.exhaustions,
+                        sc.min_left_us.map(|l| l.to_string()).unwrap_or_default(),
+                        sc.prio_changes
+                    )?;
+                }
+                out.flush()
+            }
+        }
+    }
+}
+
+// one column with the CPU time per CPU seen in the trace
+fn write_rows(path: &Path, rows: &[SchedRow], cpus: &BTreeSet<u8>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "tid,pid,task,name")?;
+    for cpu in cpus {
+        write!(out, ",cpu{cpu}_ns")?;
+    }
+    writeln!(out, ",total_ns,switches,preemptions,blocks,avg_slice_ns")?;
+
+    for row in rows {
+        write!(
+            out,
+            "{},{},\"{}\",\"{}\"",
+            row.tid,
+            row.pid,
+            row.task.replace('"', "\"\""),
+            row.name.replace('"', "\"\"")
+        )?;
+        for cpu in cpus {
+            write!(out, ",{}", row.cpu_time_ns.get(cpu).copied().unwrap_or(0))?;
+        }
+        writeln!(
+            out,
+            ",{},{},{},{},{}",
+            row.total_ns, row.switches, row.preemptions, row.blocks, row.avg_slice_ns
+        )?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thread(tid: i64, name: &str) -> ThreadInfo {
+        ThreadInfo {
+            tid,
+            pid: tid,
+            name: name.to_string(),
+            task: name.to_string(),
+            state: None,
+            prio: None,
+        }
+    }
+
+    #[test]
+    fn first_slice_not_counted() {
+        let (idle, a, b) = (thread(IDLE_TID, "idle"), thread(1, "a"), thread(2, "b"));
+        let mut stats = SchedStats::new(1_000_000_000);
+        // `a` ran since before the trace
+        stats.record_switch(0, 100, &a, &b, true);
+        stats.record_switch(0, 300, &b, &a, true);
+        stats.record_switch(0, 600, &a, &idle, false);
+
+        let report = stats.report(&KernelObjectMap::default());
+        let a = &report.threads[0];
+        assert_eq!((a.tid, a.total_ns, a.preemptions, a.blocks), (1, 300, 0, 1));
+        let b = &report.threads[1];
+        assert_eq!((b.tid, b.total_ns, b.preemptions, b.blocks), (2, 200, 1, 0));
+        assert_eq!(report.cpus[0].observed_ns, 500);
+    }
+}
@@ -0,0 +1,16 @@
+//! The source component class `tbuf` of the babeltrace2 plugin `fiasco`, which decodes the raw
+//! tbuf events of a capture file or of a TCP connection like the one of the CLI:
+//!
+//! ```text
+//! babeltrace2 --plugin-path target/release \
+//!     --component source.fiasco.tbuf --params 'path="capture.bin",clock-frequency=2000000000'
+//! babeltrace2 --plugin-path target/release \
+//!     --component source.fiasco.tbuf --params 'port=8888,clock-frequency=2000000000,cpus=4' \
+//!     --component sink.ctf.fs --params 'path="ctf_trace"'
+//! ```
+//!
+//! Only built with the `plugin` feature. The plugin descriptors and the shared library are the
+//! `babeltrace-plugin-fiasco` crate in `plugin/`, so the CLI doesn't get the exported symbols of
+//! a second plugin.
+
+pub mod source;
@@ -0,0 +1,470 @@
+//! The `source.fiasco.tbuf` component. It reads the 128 byte tbuf events of its input, decodes
+//! them with the [`EventParser`], enriches them with an [`Enricher`] and converts them per CPU with
+//! a [`TrcPluginState`], the same converter the CLI uses. Every CPU has an output port (`cpu0`,
+//! `cpu1`, ...) with its own stream.
+//!
+//! Parameters:
+//!
+//! - `path` (string): raw capture file, the bytes as received by the CLI
+//! - `port` (integer): TCP port to listen on for the target, instead of `path`
+//! - `clock-frequency` (integer, required): the clock frequency of the timestamps
+//! - `cpus` (integer, default 1): number of CPUs. The events of other CPUs only update the kernel
+//!   objects and are dropped, with a warning for each such CPU and their number when the
+//!   component is finalized.
+//! - `clock-name`, `trace-name` (strings): as `--clock-name` and `--trace-name` of the CLI
+//! - `names`, `symbols` (strings): as `--names` and `--symbols` of the CLI
+//!
+//! There is no filtering or sampling as with `--include`, `--exclude`, `--sample` and
+//! `--rate-limit` of the CLI, every event is converted. Filter the messages with babeltrace
+//! components instead.
+
+use crate::converter::TraceParams;
+use crate::converter::interruptor::Interruptor;
+use crate::converter::kernel_object::KernelObjectMap;
+use crate::converter::plugin::TrcPluginState;
+use crate::enrich::Enricher;
+use crate::names;
+use crate::parser::EventParser;
+use crate::sampling::Sampler;
+use crate::sink::SinkEvent;
+use crate::symbols::Symbolizer;
+use babeltrace2_sys::{Error, MessageIteratorStatus, ffi};
+use log::{debug, error, info, warn};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::{CStr, CString, c_void};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::{ptr, slice};
+
+pub type InitializeMethod =
+    unsafe extern "C" fn(
+        *mut ffi::bt_self_component_source,
+        *mut ffi::bt_self_component_source_configuration,
+        *const ffi::bt_value,
+        *mut c_void,
+    ) -> ffi::bt_component_class_initialize_method_status;
+pub type FinalizeMethod = unsafe extern "C" fn(*mut ffi::bt_self_component_source);
+pub type IteratorInitializeMethod =
+    unsafe extern "C" fn(
+        *mut ffi::bt_self_message_iterator,
+        *mut ffi::bt_self_message_iterator_configuration,
+        *mut ffi::bt_self_component_port_output,
+    ) -> ffi::bt_message_iterator_class_initialize_method_status;
+pub type IteratorFinalizeMethod = unsafe extern "C" fn(*mut ffi::bt_self_message_iterator);
+pub type IteratorNextMethod =
+    unsafe extern "C" fn(
+        *mut ffi::bt_self_message_iterator,
+        *mut *const ffi::bt_message,
+        u64,
+        *mut u64,
+    ) -> ffi::bt_message_iterator_class_next_method_status;
+
+pub const HELP: &CStr = c"Parameters: path=\"<capture file>\" or port=<TCP port>, \
+clock-frequency=<Hz>, optional cpus=<number of CPUs> (events of other CPUs are dropped), \
+clock-name, trace-name, names and symbols. No event filtering or sampling is applied.";
+
+const DEFAULT_CPUS: u64 = 1;
+
+pub enum Input {
+    File(PathBuf),
+    Port(u16),
+}
+
+/// Parameters of a `source.fiasco.tbuf` component
+pub struct TbufParams {
+    pub input: Input,
+    pub trace: TraceParams,
+    pub cpus: u8,
+    pub names: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+}
+
+impl TbufParams {
+    /// Reads the parameters from the map value given to the component
+    ///
+    /// # Safety
+    ///
+    /// `params` has to be a valid map value
+    pub unsafe fn from_value(params: *const ffi::bt_value) -> Result<Self, Error> {
+        let param = |name: &CStr| unsafe {
+            let value = ffi::bt_value_map_borrow_entry_value_const(params, name.as_ptr());
+            (!value.is_null()).then_some(value)
+        };
+        let string = |name: &CStr| -> Result<Option<String>, Error> {
+            let Some(value) = param(name) else {
+                return Ok(None);
+            };
+            unsafe {
+                if ffi::bt_value_get_type(value) != ffi::bt_value_type::BT_VALUE_TYPE_STRING {
+                    return Err(invalid(name, "a string"));
+                }
+                Ok(Some(
+                    CStr::from_ptr(ffi::bt_value_string_get(value))
+                        .to_str()?
+                        .to_string(),
+                ))
+            }
+        };
+        let integer = |name: &CStr| -> Result<Option<u64>, Error> {
+            let Some(value) = param(name) else {
+                return Ok(None);
+            };
+            unsafe {
+                match ffi::bt_value_get_type(value) {
+                    ffi::bt_value_type::BT_VALUE_TYPE_UNSIGNED_INTEGER => {
+                        Ok(Some(ffi::bt_value_integer_unsigned_get(value)))
+                    }
+                    ffi::bt_value_type::BT_VALUE_TYPE_SIGNED_INTEGER => {
+                        u64::try_from(ffi::bt_value_integer_signed_get(value))
+                            .map(Some)
+                            .map_err(|_| invalid(name, "a positive integer"))
+                    }
+                    _ => Err(invalid(name, "an integer")),
+                }
+            }
+        };
+
+        let input = match (string(c"path")?, integer(c"port")?) {
+            (Some(path), None) => Input::File(path.into()),
+            (None, Some(port)) => {
+                Input::Port(u16::try_from(port).map_err(|_| invalid(c"port", "a TCP port number"))?)
+            }
+            _ => {
+                return Err(Error::PluginError(
+                    "Either the `path` or the `port` parameter is required".to_owned(),
+                ));
+            }
+        };
+        let clock_frequency = integer(c"clock-frequency")?.ok_or_else(|| {
+            Error::PluginError("The `clock-frequency` parameter is required".to_owned())
+        })?;
+        let cpus = integer(c"cpus")?.unwrap_or(DEFAULT_CPUS);
+
+        Ok(Self {
+            input,
+            trace: TraceParams {
+                clock_name: string(c"clock-name")?.unwrap_or_else(|| "monotonic".to_owned()),
+                clock_frequency,
+                trace_name: string(c"trace-name")?.unwrap_or_else(|| "l4re".to_owned()),
+            },
+            cpus: u8::try_from(cpus)
+                .ok()
+                .filter(|&c| c > 0)
+                .ok_or_else(|| invalid(c"cpus", "a number of CPUs between 1 and 255"))?,
+            names: string(c"names")?.map(PathBuf::from),
+            symbols: string(c"symbols")?.map(PathBuf::from),
+        })
+    }
+}
+
+fn invalid(name: &CStr, expected: &str) -> Error {
+    Error::PluginError(format!(
+        "The `{}` parameter must be {expected}",
+        name.to_string_lossy()
+    ))
+}
+
+/// The data of a component: the input and the queued events of every CPU
+pub struct TbufSource {
+    reader: Option<Box<dyn Read>>,
+    // accepts the connection of the target on the first read
+    listener: Option<TcpListener>,
+    queues: Vec<Rc<RefCell<VecDeque<SinkEvent>>>>,
+    eof: Rc<Cell<bool>>,
+    trace: TraceParams,
+    enricher: Enricher,
+    // events of CPUs without an output port, per CPU
+    dropped: BTreeMap<u8, u64>,
+}
+
+impl TbufSource {
+    pub fn new(params: TbufParams) -> Result<Self, Error> {
+        let (reader, listener): (Option<Box<dyn Read>>, _) = match &params.input {
+            Input::File(path) => {
+                let file = File::open(path).map_err(|e| {
+                    Error::PluginError(format!("Could not open {}: {e}", path.display()))
+                })?;
+                (Some(Box::new(BufReader::new(file))), None)
+            }
+            Input::Port(port) => {
+                let listener = TcpListener::bind(("0.0.0.0", *port)).map_err(|e| {
+                    Error::PluginError(format!("Could not bind to port {port}: {e}"))
+                })?;
+                (None, Some(listener))
+            }
+        };
+
+        let mut object_map = KernelObjectMap::default();
+        if let Some(path) = &params.names {
+            let presets = names::load(path).map_err(|e| {
+                Error::PluginError(format!("Could not load names file {path:?} ({e})"))
+            })?;
+            for preset in presets {
+                object_map.preset(preset);
+            }
+        }
+        let symbolizer = Symbolizer::load(params.symbols.as_deref(), &[])
+            .map_err(|e| Error::PluginError(format!("Could not load symbols ({e})")))?;
+
+        Ok(Self {
+            reader,
+            listener,
+            queues: (0..params.cpus).map(|_| Default::default()).collect(),
+            eof: Rc::new(Cell::new(false)),
+            enricher: Enricher::new(
+                object_map,
+                symbolizer,
+                Default::default(),
+                Sampler::new(Vec::new(), params.trace.clock_frequency),
+                params.trace.clock_frequency,
+            ),
+            trace: params.trace,
+            dropped: BTreeMap::new(),
+        })
+    }
+
+    // reads events until there is one of `cpu` or the input ends
+    fn fill(&mut self, cpu: u8) {
+        while self.queues[cpu as usize].borrow().is_empty() && !self.eof.get() {
+            if self.reader.is_none() {
+                let Some(listener) = self.listener.take() else {
+                    self.eof.set(true);
+                    break;
+                };
+                match listener.accept() {
+                    Ok((stream, addr)) => {
+                        info!("Accepted connection from {:?}", addr);
+                        self.reader = Some(Box::new(BufReader::new(stream)));
+                    }
+                    Err(e) => {
+                        error!("Error accepting TCP connection ({:?})", e);
+                        self.eof.set(true);
+                        break;
+                    }
+                }
+            }
+
+            let reader = self.reader.as_mut().unwrap();
+            match EventParser::next_event(reader) {
+                Ok(Some(event)) => {
+                    let event_cpu = event.event_common().cpu;
+                    let event = self.enricher.process(event);
+                    let Some(queue) = self.queues.get(event_cpu as usize) else {
+                        let dropped = self.dropped.entry(event_cpu).or_default();
+                        if *dropped == 0 {
+                            warn!(
+                                "Dropping the events of CPU {event_cpu}, the component has {} \
+                                 CPUs (parameter cpus)",
+                                self.queues.len()
+                            );
+                        }
+                        *dropped += 1;
+                        continue;
+                    };
+                    if let Some(event) = event {
+                        queue.borrow_mut().push_back(event);
+                    }
+                }
+                Ok(None) => {
+                    debug!("End of input");
+                    self.eof.set(true);
+                }
+                Err(e) => warn!("Could not parse event ({:?})", e),
+            }
+        }
+    }
+}
+
+/// The data of a message iterator, the converter of the CPU of its port
+struct TbufIterator {
+    cpu: u8,
+    state: TrcPluginState,
+    started: bool,
+}
+
+unsafe fn source_of<'a>(msg_iter: *mut ffi::bt_self_message_iterator) -> &'a mut TbufSource {
+    unsafe {
+        let component = ffi::bt_self_message_iterator_borrow_component(msg_iter);
+        &mut *(ffi::bt_self_component_get_data(component) as *mut TbufSource)
+    }
+}
+
+/// Creates the [`TbufSource`] of a component from its parameters
+///
+/// # Safety
+///
+/// Only to be called by babeltrace, as the initialize method of the component class
+pub unsafe extern "C" fn initialize(
+    self_component: *mut ffi::bt_self_component_source,
+    _config: *mut ffi::bt_self_component_source_configuration,
+    params: *const ffi::bt_value,
+    _init_method_data: *mut c_void,
+) -> ffi::bt_component_class_initialize_method_status {
+    use ffi::bt_component_class_initialize_method_status::*;
+
+    // the plugin is loaded by babeltrace, so nobody else sets up the logger
+    let _ = env_logger::try_init();
+
+    let source = unsafe { TbufParams::from_value(params) }.and_then(TbufSource::new);
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => {
+            error!("Could not initialize source.fiasco.tbuf ({:?})", e);
+            return BT_COMPONENT_CLASS_INITIALIZE_METHOD_STATUS_ERROR;
+        }
+    };
+
+    for cpu in 0..source.queues.len() {
+        let name = CString::new(format!("cpu{cpu}")).unwrap();
+        let ret = unsafe {
+            ffi::bt_self_component_source_add_output_port(
+                self_component,
+                name.as_ptr(),
+                cpu as *mut c_void,
+                ptr::null_mut(),
+            )
+        };
+        if ret != ffi::bt_self_component_add_port_status::BT_SELF_COMPONENT_ADD_PORT_STATUS_OK {
+            error!("Could not add output port {:?}", name);
+            return BT_COMPONENT_CLASS_INITIALIZE_METHOD_STATUS_ERROR;
+        }
+    }
+
+    unsafe {
+        ffi::bt_self_component_set_data(
+            self_component as *mut ffi::bt_self_component,
+            Box::into_raw(Box::new(source)) as *mut c_void,
+        );
+    }
+    BT_COMPONENT_CLASS_INITIALIZE_METHOD_STATUS_OK
+}
+
+/// Drops the [`TbufSource`] of a component
+///
+/// # Safety
+///
+/// Only to be called by babeltrace, as the finalize method of the component class
+pub unsafe extern "C" fn finalize(self_component: *mut ffi::bt_self_component_source) {
+    unsafe {
+        let data = ffi::bt_self_component_get_data(self_component as *mut ffi::bt_self_component);
+        let source = Box::from_raw(data as *mut TbufSource);
+        for (cpu, dropped) in &source.dropped {
+            warn!("Dropped {dropped} events of CPU {cpu}");
+        }
+    }
+}
+
+/// Creates the stream of the CPU of an output port
+///
+/// # Safety
+///
+/// Only to be called by babeltrace, as the message iterator initialize method of the component
+/// class
+pub unsafe extern "C" fn iterator_initialize(
+    msg_iter: *mut ffi::bt_self_message_iterator,
+    _config: *mut ffi::bt_self_message_iterator_configuration,
+    port: *mut ffi::bt_self_component_port_output,
+) -> ffi::bt_message_iterator_class_initialize_method_status {
+    use ffi::bt_message_iterator_class_initialize_method_status::*;
+
+    let source = unsafe { source_of(msg_iter) };
+    let cpu =
+        unsafe { ffi::bt_self_component_port_get_data(port as *mut ffi::bt_self_component_port) }
+            as usize as u8;
+
+    let state = TrcPluginState::new(
+        Interruptor::new(),
+        source.queues[cpu as usize].clone(),
+        &source.trace,
+        source.eof.clone(),
+        Default::default(),
+        cpu,
+        source.enricher.kernel_object_map(),
+    )
+    .and_then(|mut state| {
+        let component = unsafe { ffi::bt_self_message_iterator_borrow_component(msg_iter) };
+        state.init(component)?;
+        Ok(state)
+    });
+    let state = match state {
+        Ok(state) => state,
+        Err(e) => {
+            error!("Could not create the stream of CPU {cpu} ({:?})", e);
+            return BT_MESSAGE_ITERATOR_CLASS_INITIALIZE_METHOD_STATUS_ERROR;
+        }
+    };
+
+    let iter = TbufIterator {
+        cpu,
+        state,
+        started: false,
+    };
+    unsafe {
+        ffi::bt_self_message_iterator_set_data(
+            msg_iter,
+            Box::into_raw(Box::new(iter)) as *mut c_void,
+        );
+    }
+    BT_MESSAGE_ITERATOR_CLASS_INITIALIZE_METHOD_STATUS_OK
+}
+
+/// Releases the stream of a message iterator
+///
+/// # Safety
+///
+/// Only to be called by babeltrace, as the message iterator finalize method of the component
+/// class
+pub unsafe extern "C" fn iterator_finalize(msg_iter: *mut ffi::bt_self_message_iterator) {
+    let mut iter = unsafe {
+        Box::from_raw(ffi::bt_self_message_iterator_get_data(msg_iter) as *mut TbufIterator)
+    };
+    if let Err(e) = iter.state.release() {
+        error!("Could not release the stream of CPU {} ({:?})", iter.cpu, e);
+    }
+}
+
+/// Fills `msgs` with the messages of the next events of the CPU
+///
+/// # Safety
+///
+/// Only to be called by babeltrace, as the message iterator next method of the component class
+pub unsafe extern "C" fn iterator_next(
+    msg_iter: *mut ffi::bt_self_message_iterator,
+    msgs: *mut *const ffi::bt_message,
+    capacity: u64,
+    count: *mut u64,
+) -> ffi::bt_message_iterator_class_next_method_status {
+    use ffi::bt_message_iterator_class_next_method_status::*;
+
+    let iter =
+        unsafe { &mut *(ffi::bt_self_message_iterator_get_data(msg_iter) as *mut TbufIterator) };
+    let source = unsafe { source_of(msg_iter) };
+
+    source.fill(iter.cpu);
+    if source.queues[iter.cpu as usize].borrow().is_empty() {
+        // a CPU without events never opened its stream
+        if !iter.started {
+            return BT_MESSAGE_ITERATOR_CLASS_NEXT_METHOD_STATUS_END;
+        }
+    } else {
+        iter.started = true;
+    }
+
+    let messages = unsafe { slice::from_raw_parts_mut(msgs, capacity as usize) };
+    match iter.state.next(msg_iter, messages) {
+        Ok(MessageIteratorStatus::Messages(n)) => {
+            unsafe { *count = n };
+            BT_MESSAGE_ITERATOR_CLASS_NEXT_METHOD_STATUS_OK
+        }
+        Ok(MessageIteratorStatus::NoMessages) => BT_MESSAGE_ITERATOR_CLASS_NEXT_METHOD_STATUS_AGAIN,
+        Ok(MessageIteratorStatus::Done) => BT_MESSAGE_ITERATOR_CLASS_NEXT_METHOD_STATUS_END,
+        Err(e) => {
+            error!("Could not convert the events of CPU {} ({:?})", iter.cpu, e);
+            BT_MESSAGE_ITERATOR_CLASS_NEXT_METHOD_STATUS_ERROR
+        }
+    }
+}
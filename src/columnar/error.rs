@@ -0,0 +1,25 @@
+use arrow_schema::ArrowError;
+use parquet::errors::ParquetError;
+use std::fmt::Display;
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("IO error on writing the tables")]
+    Io(#[from] io::Error),
+    #[error("Could not write the Parquet file")]
+    Parquet(#[from] ParquetError),
+    #[error("Could not build the record batch")]
+    Arrow(#[from] ArrowError),
+    #[error("A {0} can't be written to a table")]
+    Unsupported(&'static str),
+    #[error("Could not serialize the row: {0}")]
+    Serialize(String),
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Serialize(msg.to_string())
+    }
+}
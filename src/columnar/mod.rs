@@ -0,0 +1,269 @@
+//! Parquet export (`--parquet <dir>`) for the analysis of traces with pandas, polars and the
+//! like.
+//!
+//! Every event type gets its own table (`<event>.parquet`, e.g. `ipc.parquet`) with the fields
+//! of [`EventCommon`] followed by the payload fields of its struct in `src/event/`. Arrays are
+//! split into one column per element (`dword_0`, `dword_1`), the char arrays are strings. Payload
+//! fields with the name of a common field get the prefix `payload_`. Padding is left out.
+//!
+//! `kernel_objects.parquet` holds the final kernel object table, see [`object_table`].
+
+pub mod error;
+pub mod row;
+
+use crate::converter::kernel_object::KernelObjectMap;
+use crate::names::ObjectEntry;
+use crate::names::export::object_table;
+use crate::sink::{SinkEvent, TraceSink};
+use arrow_array::builder::{
+    BooleanBuilder, Int8Builder, Int16Builder, Int32Builder, Int64Builder, StringBuilder,
+    UInt8Builder, UInt16Builder, UInt32Builder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt32Array, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use error::Error;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use row::{Row, Scalar};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// rows buffered per table before they are written as a row group
+const BATCH_ROWS: usize = 64 * 1024;
+
+macro_rules! columns {
+    ($($variant:ident($builder:ident, $data_type:expr)),* $(,)?) => {
+        enum Column {
+            $($variant($builder),)*
+        }
+
+        impl Column {
+            fn new(value: &Scalar) -> Self {
+                match value {
+                    $(Scalar::$variant(_) => Column::$variant($builder::new()),)*
+                }
+            }
+
+            fn data_type(&self) -> DataType {
+                match self {
+                    $(Column::$variant(_) => $data_type,)*
+                }
+            }
+
+            // a value of another type than the first one of the column becomes null
+            fn append(&mut self, value: Option<&Scalar>) {
+                match (self, value) {
+                    $((Column::$variant(b), Some(Scalar::$variant(v))) => b.append_value(v.clone()),)*
+                    $((Column::$variant(b), _) => b.append_null(),)*
+                }
+            }
+
+            fn finish(&mut self) -> ArrayRef {
+                match self {
+                    $(Column::$variant(b) => Arc::new(b.finish()),)*
+                }
+            }
+        }
+    };
+}
+
+columns!(
+    Bool(BooleanBuilder, DataType::Boolean),
+    I8(Int8Builder, DataType::Int8),
+    I16(Int16Builder, DataType::Int16),
+    I32(Int32Builder, DataType::Int32),
+    I64(Int64Builder, DataType::Int64),
+    U8(UInt8Builder, DataType::UInt8),
+    U16(UInt16Builder, DataType::UInt16),
+    U32(UInt32Builder, DataType::UInt32),
+    U64(UInt64Builder, DataType::UInt64),
+    Str(StringBuilder, DataType::Utf8),
+);
+
+/// The table of one event type, its columns are given by the first row
+struct Table {
+    path: PathBuf,
+    schema: SchemaRef,
+    columns: Vec<Column>,
+    rows: usize,
+    writer: ArrowWriter<File>,
+}
+
+impl Table {
+    fn create(path: &Path, row: &Row) -> Result<Self, Error> {
+        let columns: Vec<Column> = row.columns.iter().map(|(_, v)| Column::new(v)).collect();
+        let fields: Vec<Field> = row
+            .columns
+            .iter()
+            .zip(&columns)
+            .map(|((name, _), c)| Field::new(name, c.data_type(), true))
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(properties()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            schema,
+            columns,
+            rows: 0,
+            writer,
+        })
+    }
+
+    fn push(&mut self, row: &Row) -> Result<(), Error> {
+        for (i, column) in self.columns.iter_mut().enumerate() {
+            column.append(row.columns.get(i).map(|(_, v)| v));
+        }
+        self.rows += 1;
+        if self.rows >= BATCH_ROWS {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let arrays = self.columns.iter_mut().map(|c| c.finish()).collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.writer.write(&batch)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<(), Error> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(())
+    }
+
+    /// Closes the table, a file that could not be completed has no footer and is removed
+    fn close_or_remove(self) -> Result<(), Error> {
+        let path = self.path.clone();
+        self.close().inspect_err(|_| {
+            let _ = fs::remove_file(&path);
+        })
+    }
+}
+
+/// Writes the events to one Parquet table per event type, see the module documentation
+pub struct ParquetExport {
+    dir: PathBuf,
+    tables: HashMap<String, Table>,
+    // the first error, later events are skipped
+    error: Option<Error>,
+}
+
+impl ParquetExport {
+    pub fn create(dir: &Path) -> Result<Self, Error> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            tables: HashMap::new(),
+            error: None,
+        })
+    }
+
+    fn row(event: &SinkEvent) -> Result<Row, Error> {
+        let mut row = Row::from_value(&event.event.event_common(), &[])?;
+        let payload = Row::from_value(&event.event, &["common"])?;
+        for (name, value) in payload.columns {
+            let name = if row.columns.iter().any(|(n, _)| *n == name) {
+                format!("payload_{name}")
+            } else {
+                name
+            };
+            row.columns.push((name, value));
+        }
+        Ok(row)
+    }
+
+    fn write(&mut self, event: &SinkEvent) -> Result<(), Error> {
+        let row = Self::row(event)?;
+        let name = event.event.to_string().to_lowercase();
+        if !self.tables.contains_key(&name) {
+            let path = self.dir.join(format!("{name}.parquet"));
+            self.tables
+                .insert(name.clone(), Table::create(&path, &row)?);
+        }
+        self.tables.get_mut(&name).unwrap().push(&row)
+    }
+}
+
+impl TraceSink for ParquetExport {
+    fn event(&mut self, event: &SinkEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.write(event) {
+            self.error = Some(e);
+        }
+    }
+
+    fn kernel_objects(&mut self, objects: &KernelObjectMap) {
+        if self.error.is_some() {
+            return;
+        }
+        let path = self.dir.join("kernel_objects.parquet");
+        if let Err(e) = write_objects(&path, &object_table(objects)) {
+            self.error = Some(e);
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), crate::sink::error::Error> {
+        // close all tables, also after an error, and report the first error
+        let mut result = self.error.take().map_or(Ok(()), Err);
+        for (_, table) in self.tables.drain() {
+            let closed = table.close_or_remove();
+            result = result.and(closed);
+        }
+        Ok(result?)
+    }
+}
+
+/// Writes the kernel object table to `path`
+pub fn write_objects(path: &Path, entries: &[ObjectEntry]) -> Result<(), Error> {
+    let u64_column = |f: fn(&ObjectEntry) -> Option<u64>| -> ArrayRef {
+        Arc::new(entries.iter().map(f).collect::<UInt64Array>())
+    };
+    let str_column = |f: fn(&ObjectEntry) -> Option<&str>| -> ArrayRef {
+        Arc::new(entries.iter().map(f).collect::<StringArray>())
+    };
+
+    let batch = RecordBatch::try_from_iter([
+        ("id", u64_column(|e| e.id)),
+        ("addr", u64_column(|e| e.addr)),
+        ("type", str_column(|e| e.kobj_type.as_deref())),
+        ("name", str_column(|e| Some(&e.name))),
+        ("task", u64_column(|e| e.task)),
+        ("thread", u64_column(|e| e.thread)),
+        ("prio", u64_column(|e| e.prio)),
+        ("state", str_column(|e| e.state.as_deref())),
+        ("created", u64_column(|e| e.created)),
+        ("destroyed", u64_column(|e| e.destroyed)),
+        (
+            "generation",
+            Arc::new(
+                entries
+                    .iter()
+                    .map(|e| e.generation)
+                    .collect::<UInt32Array>(),
+            ) as ArrayRef,
+        ),
+    ])?;
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(properties()))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn properties() -> WriterProperties {
+    WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build()
+}
@@ -0,0 +1,280 @@
+use super::error::Error;
+use serde::Serialize;
+use serde::ser::{self, Impossible};
+
+/// A single value of a column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    Str(String),
+}
+
+/// The columns of a serialized struct. Fields of nested structs are named `<field>_<nested>`,
+/// elements of arrays `<field>_<index>`.
+#[derive(Debug, Default)]
+pub struct Row {
+    pub columns: Vec<(String, Scalar)>,
+    // name of the field which is serialized
+    name: String,
+    // top level fields to leave out
+    skip: &'static [&'static str],
+}
+
+impl Row {
+    pub fn from_value<T: Serialize>(
+        value: &T,
+        skip: &'static [&'static str],
+    ) -> Result<Self, Error> {
+        let mut row = Row {
+            skip,
+            ..Default::default()
+        };
+        value.serialize(&mut row)?;
+        Ok(row)
+    }
+
+    fn push(&mut self, value: Scalar) -> Result<(), Error> {
+        self.columns.push((self.name.clone(), value));
+        Ok(())
+    }
+
+    fn nested<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        let len = self.name.len();
+        if len > 0 {
+            self.name.push('_');
+        }
+        self.name.push_str(name);
+        let res = value.serialize(&mut *self);
+        self.name.truncate(len);
+        res
+    }
+}
+
+/// Serializes the elements of an array
+pub struct Elements<'a> {
+    row: &'a mut Row,
+    index: usize,
+}
+
+impl Elements<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let index = self.index.to_string();
+        self.index += 1;
+        self.row.nested(&index, value)
+    }
+}
+
+impl ser::SerializeSeq for Elements<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for Elements<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Row {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        if self.name.is_empty() && self.skip.contains(&key) {
+            return Ok(());
+        }
+        self.nested(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Row {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Elements<'a>;
+    type SerializeTuple = Elements<'a>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.push(Scalar::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.push(Scalar::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.push(Scalar::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.push(Scalar::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.push(Scalar::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.push(Scalar::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.push(Scalar::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.push(Scalar::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.push(Scalar::U64(v))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), Error> {
+        Err(Error::Unsupported("f32"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Err(Error::Unsupported("f64"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.push(Scalar::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.push(Scalar::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(Error::Unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(Error::Unsupported("option"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), Error> {
+        Err(Error::Unsupported("option"))
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(Error::Unsupported("enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Elements<'a>, Error> {
+        Ok(Elements {
+            row: self,
+            index: 0,
+        })
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Elements<'a>, Error> {
+        Ok(Elements {
+            row: self,
+            index: 0,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::Unsupported("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Unsupported("enum"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::Unsupported("map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Unsupported("enum"))
+    }
+}
@@ -0,0 +1,12 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Could not open the input ({0})")]
+    Input(io::Error),
+    #[error("Could not instantiate the converter of CPU {0} ({1})")]
+    Converter(u8, String),
+    #[error("Could not merge the traces of the CPUs ({0})")]
+    Merge(#[from] io::Error),
+}
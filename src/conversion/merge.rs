@@ -0,0 +1,91 @@
+//! Merging of the per-CPU traces written by the converters into one trace
+
+use crate::converter::cpu_output;
+use crate::metadata::{self, CtfVersion};
+use log::debug;
+use regex::Regex;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub fn merge_traces(cpus: &[u8], path: &Path, ctf_version: CtfVersion) -> Result<(), io::Error> {
+    // Define input directories and output
+    let out_dir = path;
+
+    // Create output directory if it doesn't exist
+    if !out_dir.exists() {
+        fs::create_dir(out_dir)?;
+    }
+
+    // Move and rename stream files
+    for cpu in cpus {
+        let src_dir = cpu_output(path, *cpu);
+        let stream_file = src_dir.join("stream");
+        let dest_file = out_dir.join(format!("stream_{}", cpu));
+        fs::copy(&stream_file, &dest_file)?;
+    }
+
+    if ctf_version == CtfVersion::V2 {
+        return merge_ctf2_metadata(cpus, path, out_dir);
+    }
+
+    // Merge metadata files
+    let mut merged = String::new();
+    let mut seen_sections = Vec::new();
+    let section_re = Regex::new(r"^(trace|env|clock) \{").unwrap();
+    let mut include_lines = true;
+
+    for cpu in cpus {
+        let meta_path = cpu_output(path, *cpu).join("metadata");
+        let mut file = fs::File::open(&meta_path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        for line in content.lines() {
+            if let Some(cap) = section_re.captures(line) {
+                let sec = cap.get(1).unwrap().as_str();
+                if seen_sections.contains(&sec.to_string()) {
+                    // Skip lines until matching closing brace
+                    include_lines = false;
+                } else {
+                    seen_sections.push(sec.to_string());
+                    include_lines = true;
+                }
+            }
+
+            if include_lines {
+                merged.push_str(line);
+                merged.push('\n');
+            }
+
+            // Detect end of section
+            if !include_lines && line.trim() == "};" {
+                include_lines = true;
+            }
+        }
+    }
+
+    // Write merged metadata
+    let mut out_meta = fs::File::create(out_dir.join("metadata"))?;
+    out_meta.write_all(merged.as_bytes())?;
+
+    debug!("Merged CTF streams into {:?}", out_dir);
+    Ok(())
+}
+
+// parses the TSDL metadata of all CPUs and writes the merged metadata in the CTF 2 format
+fn merge_ctf2_metadata(cpus: &[u8], path: &Path, out_dir: &Path) -> Result<(), io::Error> {
+    let mut traces = Vec::new();
+    for cpu in cpus {
+        let meta_path = cpu_output(path, *cpu).join("metadata");
+        let content = fs::read_to_string(&meta_path)?;
+        traces.push(metadata::tsdl::Metadata::parse(&content).map_err(io::Error::other)?);
+    }
+
+    let merged = metadata::merge(traces);
+    let json = metadata::ctf2::to_json_sequence(&merged).map_err(io::Error::other)?;
+    fs::write(out_dir.join("metadata"), json)?;
+
+    debug!("Merged CTF 2 streams into {:?}", out_dir);
+    Ok(())
+}
@@ -0,0 +1,478 @@
+//! The whole conversion as a builder: where the raw events come from, how the CTF trace is
+//! written and which [`TraceSink`]s get the converted events.
+//!
+//! ```no_run
+//! use l4re_tracestream::conversion::{Conversion, Input};
+//! use l4re_tracestream::sink::dump::{DumpFormat, EventDump};
+//! use std::cell::RefCell;
+//! use std::rc::Rc;
+//!
+//! let dump = EventDump::create(DumpFormat::Jsonl, None, 2_000_000_000).unwrap();
+//! let summary = Conversion::new(2_000_000_000)
+//!     .input(Input::File("capture.bin".into()))
+//!     .output("ctf_trace")
+//!     .sink(Rc::new(RefCell::new(dump)))
+//!     .run()
+//!     .unwrap();
+//! println!("{} events on {} CPUs", summary.events, summary.cpus.len());
+//! ```
+//!
+//! A thread reads the 128 byte events from the input, a second one parses them. The
+//! [`Enricher`], the CTF writer and the sinks run on the thread calling [`Conversion::run`], so
+//! sinks don't need to be `Send`. With [`Conversion::ctf`] off, only the sinks get the events and
+//! babeltrace isn't used at all.
+
+pub mod error;
+pub mod merge;
+
+use crate::analysis::ipc_latency::IpcLatency;
+use crate::converter::interruptor::Interruptor;
+use crate::converter::kernel_object::{KernelObjectMap, ObjectPreset};
+use crate::converter::{ConverterParams, CtfSink, TraceParams};
+use crate::enrich::Enricher;
+use crate::event::Event;
+use crate::filter::EventFilter;
+use crate::metadata::CtfVersion;
+use crate::opts::Opts;
+use crate::parser::EventParser;
+use crate::sampling::{SampleRule, Sampler};
+use crate::sink::TraceSink;
+use crate::symbols::Symbolizer;
+use babeltrace2_sys::LoggingLevel;
+use error::Error;
+use log::{debug, error, info, warn};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+/// Address the CLI listens on for the target
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:8888";
+
+const EVENT_SIZE: usize = 128;
+
+/// Where the raw tbuf events come from
+pub enum Input {
+    /// Listen on the address and receive the events of the first connection
+    Tcp(String),
+    /// A capture of the received bytes
+    File(PathBuf),
+    Reader(Box<dyn Read + Send>),
+}
+
+/// Counters and the final state of a finished [`Conversion`]
+pub struct Summary {
+    /// The CPUs of which events were converted
+    pub cpus: Vec<u8>,
+    /// Number of converted events, i.e. the events handed to the CTF writer and the sinks. Events
+    /// left out by the filter or discarded by the sampler aren't counted.
+    pub events: u64,
+    /// Events missing in the event numbers
+    pub dropped_events: u64,
+    /// Events left out by the [`EventFilter`]
+    pub filtered_events: u64,
+    /// Events discarded by the [`Sampler`], per event name
+    pub discarded_events: BTreeMap<String, u64>,
+    /// Time of the first parsed event
+    pub start_time: Option<Instant>,
+    /// Received bytes per second
+    pub receive_throughput: f64,
+    pub kernel_objects: KernelObjectMap,
+    pub ipc_latency: IpcLatency,
+}
+
+pub struct Conversion {
+    input: Input,
+    params: ConverterParams,
+    ctf_version: CtfVersion,
+    ctf: bool,
+    interruptor: Interruptor,
+    presets: Vec<ObjectPreset>,
+    symbolizer: Symbolizer,
+    filter: EventFilter,
+    sampling: Vec<SampleRule>,
+    sinks: Vec<Rc<RefCell<dyn TraceSink>>>,
+}
+
+impl Conversion {
+    /// Conversion of the events received on [`DEFAULT_ADDRESS`] to a CTF 1.8 trace in
+    /// `ctf_trace`, with the defaults of the CLI
+    pub fn new(clock_frequency: u64) -> Self {
+        Self {
+            input: Input::Tcp(DEFAULT_ADDRESS.to_owned()),
+            params: ConverterParams {
+                output: PathBuf::from("ctf_trace"),
+                log_level: LoggingLevel::Warn,
+                trace: TraceParams {
+                    clock_name: "monotonic".to_owned(),
+                    clock_frequency,
+                    trace_name: "l4re".to_owned(),
+                },
+            },
+            ctf_version: CtfVersion::default(),
+            ctf: true,
+            interruptor: Interruptor::new(),
+            presets: Vec::new(),
+            symbolizer: Symbolizer::default(),
+            filter: EventFilter::default(),
+            sampling: Vec::new(),
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Conversion with the trace settings of the CLI options. Names, symbols and sinks are
+    /// loaded and created by the caller.
+    pub fn from_opts(opts: &Opts) -> Self {
+        Self {
+            params: opts.into(),
+            ctf_version: opts.ctf_version,
+            ctf: !opts.no_ctf,
+            filter: EventFilter::new(opts.include.clone(), opts.exclude.clone()),
+            sampling: [opts.sample.as_slice(), &opts.rate_limit].concat(),
+            ..Self::new(opts.clock_frequency)
+        }
+    }
+
+    pub fn input(mut self, input: Input) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Directory of the merged trace
+    pub fn output(mut self, output: impl Into<PathBuf>) -> Self {
+        self.params.output = output.into();
+        self
+    }
+
+    pub fn ctf_version(mut self, ctf_version: CtfVersion) -> Self {
+        self.ctf_version = ctf_version;
+        self
+    }
+
+    /// Whether to write the CTF trace, on by default. Without it the events only go to the sinks.
+    pub fn ctf(mut self, enabled: bool) -> Self {
+        self.ctf = enabled;
+        self
+    }
+
+    pub fn clock_name(mut self, name: impl Into<String>) -> Self {
+        self.params.trace.clock_name = name.into();
+        self
+    }
+
+    pub fn trace_name(mut self, name: impl Into<String>) -> Self {
+        self.params.trace.trace_name = name.into();
+        self
+    }
+
+    /// babeltrace2 log level
+    pub fn log_level(mut self, log_level: LoggingLevel) -> Self {
+        self.params.log_level = log_level;
+        self
+    }
+
+    /// Stops the conversion early when set, the events converted so far are written
+    pub fn interruptor(mut self, interruptor: Interruptor) -> Self {
+        self.interruptor = interruptor;
+        self
+    }
+
+    /// Kernel objects to know before the first NAM event, see [`crate::names`]
+    pub fn presets(mut self, presets: Vec<ObjectPreset>) -> Self {
+        self.presets = presets;
+        self
+    }
+
+    pub fn symbolizer(mut self, symbolizer: Symbolizer) -> Self {
+        self.symbolizer = symbolizer;
+        self
+    }
+
+    /// Events to leave out of the trace and the sinks
+    pub fn filter(mut self, filter: EventFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sampling and rate limits of event types, applied to the events kept by the filter after
+    /// their kernel objects are tracked, see [`crate::sampling`]
+    pub fn sampling(mut self, rules: Vec<SampleRule>) -> Self {
+        self.sampling = rules;
+        self
+    }
+
+    /// Adds a sink getting every converted event
+    pub fn sink(mut self, sink: Rc<RefCell<dyn TraceSink>>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Converts the events until the input ends or the conversion is interrupted, then finishes
+    /// the sinks and merges the traces of the CPUs into the output directory
+    pub fn run(self) -> Result<Summary, Error> {
+        let reader = Reader::open(self.input)?;
+
+        // network -> parser
+        let (net_tx, parser_rx) = mpsc::channel::<[u8; EVENT_SIZE]>();
+        // parser -> converter
+        let (parser_tx, converter_rx) = mpsc::channel::<Event>();
+
+        // Receive the event bytes and pass them to the parser
+        let network_handle = thread::spawn(move || {
+            let mut events_received: u64 = 0;
+            let mut start_time: Option<Instant> = None;
+
+            if let Some(mut reader) = reader.connect() {
+                let mut buf: [u8; EVENT_SIZE] = [0; EVENT_SIZE];
+
+                while reader.read_exact(&mut buf).is_ok() {
+                    if start_time.is_none() {
+                        start_time = Some(Instant::now());
+                    }
+                    events_received += 1;
+                    match net_tx.send(buf) {
+                        Ok(_) => debug!("Parsed and sent event"),
+                        Err(e) => {
+                            info!("Parser channel closed ({})", e);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            start_time.map_or(0.0, |start| {
+                ((events_received * EVENT_SIZE as u64) as f64) / start.elapsed().as_secs_f64()
+            })
+        });
+
+        // Parse the event bytes and pass the to the converter
+        let parser_handle = thread::spawn(move || {
+            let mut first_event_observed = false;
+            let mut biggest_event_num: u64 = 0;
+            let mut start_time: Option<Instant> = None;
+            let mut dropped_events_total: u64 = 0;
+
+            while let Ok(event_bytes) = parser_rx.recv() {
+                if start_time.is_none() {
+                    start_time = Some(Instant::now());
+                }
+
+                debug!("Received event bytes");
+                let mut reader = Cursor::new(event_bytes);
+                let event = EventParser::next_event(&mut reader);
+
+                match event {
+                    Ok(event) => {
+                        if let Some(e) = event {
+                            let event_number = e.event_common().number;
+                            debug!("Event count: {event_number}");
+                            if event_number > biggest_event_num || !first_event_observed {
+                                let event_diff = if first_event_observed {
+                                    event_number - biggest_event_num
+                                } else {
+                                    0
+                                };
+                                if event_diff > 1 {
+                                    dropped_events_total += event_diff - 1;
+                                    warn!(
+                                        "Dropped {} events (event num: {event_number}, biggest event num: {biggest_event_num}",
+                                        event_diff - 1
+                                    );
+                                }
+
+                                biggest_event_num = event_number;
+                                first_event_observed = true;
+                                match parser_tx.send(e) {
+                                    Ok(_) => debug!("Parsed and sent event"),
+                                    Err(e) => {
+                                        info!("Converter channel closed ({})", e);
+                                        break;
+                                    }
+                                }
+                            } else {
+                                info!(
+                                    "Found duplicate/out of order event (event nr: {event_number}, max nr: {biggest_event_num}"
+                                );
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Could not parse event ({:?})", e);
+                    }
+                }
+            }
+
+            (start_time, dropped_events_total)
+        });
+
+        // Enrich the events and pass them to the CTF writer and the sinks, on this thread as the
+        // sinks aren't Send
+        let mut object_map = KernelObjectMap::default();
+        for preset in self.presets {
+            object_map.preset(preset);
+        }
+        let clock_frequency = self.params.trace.clock_frequency;
+        let sampler = Sampler::new(self.sampling, clock_frequency);
+        let mut enricher = Enricher::new(
+            object_map,
+            self.symbolizer,
+            self.filter,
+            sampler,
+            clock_frequency,
+        );
+        let mut ctf = self.ctf.then(|| {
+            CtfSink::new(
+                self.params.clone(),
+                self.interruptor.clone(),
+                enricher.kernel_object_map(),
+            )
+        });
+        let mut cpus = BTreeSet::new();
+        let mut nr_conv_events: u64 = 0;
+
+        while let Ok(event) = converter_rx.recv() {
+            let event = enricher.process(event);
+            if let Some(left_out) = enricher.take_left_out() {
+                for sink in &self.sinks {
+                    sink.borrow_mut().left_out(&left_out);
+                }
+            }
+            let Some(event) = event else {
+                continue;
+            };
+            nr_conv_events += 1;
+
+            cpus.insert(event.cpu);
+
+            // the events of the CPU the sampler discarded before this one
+            let discarded = enricher.take_discarded(event.cpu);
+            if discarded > 0 {
+                if let Some(ctf) = &mut ctf {
+                    ctf.discarded(event.cpu, discarded);
+                }
+                for sink in &self.sinks {
+                    sink.borrow_mut().discarded(event.cpu, discarded);
+                }
+            }
+            if let Some(ctf) = &mut ctf {
+                ctf.event(&event);
+                if let Some((cpu_id, e)) = ctf.take_error() {
+                    return Err(Error::Converter(cpu_id, e));
+                }
+            }
+            for sink in &self.sinks {
+                sink.borrow_mut().event(&event);
+            }
+
+            let interrupted = match &ctf {
+                Some(ctf) => ctf.interrupted(),
+                None => self.interruptor.is_set(),
+            };
+            if interrupted {
+                break;
+            }
+        }
+        // stops the reader and parser threads when the conversion ended early
+        drop(converter_rx);
+
+        // the events discarded after the last kept event of each CPU, before the streams end
+        let pending: Vec<_> = enricher.drain_discarded().collect();
+        for (cpu_id, discarded) in pending {
+            if let Some(ctf) = &mut ctf {
+                ctf.discarded(cpu_id, discarded);
+            }
+            for sink in &self.sinks {
+                sink.borrow_mut().discarded(cpu_id, discarded);
+            }
+        }
+
+        if let Some(ctf) = &mut ctf
+            && let Err(e) = ctf.finish()
+        {
+            error!("Could not finish the CTF trace ({})", e);
+        }
+
+        for sink in &self.sinks {
+            let mut sink = sink.borrow_mut();
+            sink.kernel_objects(&enricher.kernel_object_map().borrow());
+            if let Err(e) = sink.finish() {
+                error!("Could not finish output ({})", e);
+            }
+        }
+
+        let receive_throughput = network_handle.join().unwrap();
+        let (start_time, dropped_events) = parser_handle.join().unwrap();
+
+        // return the cpus of which we saw events, so we can merge those streams
+        let cpus = match &ctf {
+            Some(ctf) => {
+                let cpus = ctf.cpus();
+                merge::merge_traces(&cpus, &self.params.output, self.ctf_version)?;
+                cpus
+            }
+            None => cpus.into_iter().collect(),
+        };
+
+        let filtered_events = enricher.filter().filtered();
+        let discarded_events = enricher.discarded().clone();
+        drop(ctf);
+        let (kernel_objects, ipc_latency) = enricher.into_state();
+        Ok(Summary {
+            cpus,
+            events: nr_conv_events,
+            dropped_events,
+            filtered_events,
+            discarded_events,
+            start_time,
+            receive_throughput,
+            kernel_objects,
+            ipc_latency,
+        })
+    }
+}
+
+// the input, opened before the threads start so errors are returned by `run`
+enum Reader {
+    Listener(TcpListener),
+    Stream(Box<dyn Read + Send>),
+}
+
+impl Reader {
+    fn open(input: Input) -> Result<Self, Error> {
+        match input {
+            Input::Tcp(address) => {
+                info!("Listening on {}", address);
+                let listener = TcpListener::bind(address).map_err(Error::Input)?;
+                Ok(Reader::Listener(listener))
+            }
+            Input::File(path) => {
+                let file = File::open(path).map_err(Error::Input)?;
+                Ok(Reader::Stream(Box::new(BufReader::new(file))))
+            }
+            Input::Reader(reader) => Ok(Reader::Stream(reader)),
+        }
+    }
+
+    // waits for the target to connect
+    fn connect(self) -> Option<Box<dyn Read + Send>> {
+        match self {
+            Reader::Listener(listener) => match listener.accept() {
+                Ok((stream, addr)) => {
+                    info!("Accepted connection from {:?}", addr);
+                    Some(Box::new(BufReader::new(stream)))
+                }
+                Err(e) => {
+                    error!("Error accepting TCP connection ({:?})", e);
+                    None
+                }
+            },
+            Reader::Stream(reader) => Some(reader),
+        }
+    }
+}
@@ -1,15 +1,20 @@
 use super::CTX_MASK;
 use super::event::ipc::Ipc;
+use super::event::ipc_flow::{IpcFlowRecv, IpcFlowSend};
 use super::event::ipc_res::IpcRes;
 use super::event::ke_bin::KeBin;
 use super::event::ke_reg::KeReg;
+use super::event::kobj::{KobjCreate, KobjDestroy};
 use super::event::nam::Nam;
+use super::event::sched_context::{SchedPreemptBudget, SchedPrioChange, SchedQuantumExpired};
 use super::event::sched_migrate_task::SchedMigrateTask;
 use super::event::sched_switch::SchedSwitch;
-use super::kernel_object::{BaseKernelObject, KernelObject};
+use super::event::statedump::{
+    StatedumpEnd, StatedumpEntry, StatedumpIpcGate, StatedumpKobject, StatedumpProcessState,
+    StatedumpStart,
+};
 use super::types::{BorrowedCtfState, StringCache};
 use crate::converter::event::ke::Ke;
-use crate::converter::kernel_object::{GateObject, ThreadObject};
 use crate::event::bp::BpEvent;
 use crate::event::drq::DrqEvent;
 use crate::event::empty::EmptyEvent;
@@ -29,35 +34,35 @@ use crate::event::vcpu::VcpuEvent;
 use crate::event::{
     Event, common::EventCommon, destroy::DestroyEvent, factory::FactoryEvent, pf::PfEvent,
 };
-use crate::helpers;
+use crate::sink::{Detail, EventContext, SinkEvent};
 use babeltrace2_sys::{BtResultExt, Error, ffi};
-use log::info;
-use std::cell::RefCell;
-use std::collections::{HashMap, hash_map::Entry};
+use std::collections::{HashMap, VecDeque, hash_map::Entry};
 use std::ptr;
-use std::rc::Rc;
 
 // macro to emit basic events which don't require special processing (basically everything which
 // uses the CtfEventClass macro)
 macro_rules! emit_event {
-    ($ev_name:ident, $evt:ty, $conv:ident, $ev:ident, $ctf_state:ident, $event_common:ident) => {{
+    ($ev_name:ident, $evt:ty, $conv:ident, $ev:ident, $ctf_state:ident, $event:ident) => {{
         let stream_class = unsafe { ffi::bt_stream_borrow_class($ctf_state.stream_mut()) };
         let event_class = $conv.event_class(stream_class, $ev_name, <$evt>::event_class)?;
-        let msg = $ctf_state.create_message(event_class, $event_common.tsc);
+        let common = $event.event.event_common();
+        let msg = $ctf_state.create_message(event_class, common.tsc);
         let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-        $conv.add_event_common_ctx($event_common, ctf_event)?;
+        $conv.add_event_common_ctx(common, &$event.context, ctf_event)?;
         $ev.emit_event(ctf_event)?;
         $ctf_state.push_message(msg)?;
     }};
 }
 
+/// Writes the [`SinkEvent`]s of a CPU as CTF messages. Everything but the formatting is done by
+/// the [`Enricher`](crate::enrich::Enricher) before.
 pub struct TrcCtfConverter {
     sched_switch_event_class: *mut ffi::bt_event_class,
     sched_migrate_task_event_class: *mut ffi::bt_event_class,
     event_classes: HashMap<String, *mut ffi::bt_event_class>,
     string_cache: StringCache,
-    kernel_object_map: Rc<RefCell<HashMap<u64, KernelObject>>>,
-    last_sched_in: Option<ThreadObject>,
+    statedump: VecDeque<StatedumpEntry>,
+    statedump_common: EventCommon,
 }
 
 impl Drop for TrcCtfConverter {
@@ -73,7 +78,10 @@ impl Drop for TrcCtfConverter {
 }
 
 impl TrcCtfConverter {
-    pub fn new(kernel_object_map: Rc<RefCell<HashMap<u64, KernelObject>>>) -> Self {
+    /// Upper bound of the messages `convert` pushes for a single event
+    pub const MAX_MESSAGES_PER_EVENT: usize = 5;
+
+    pub fn new() -> Self {
         let mut string_cache: StringCache = Default::default();
         string_cache.insert_str("").unwrap();
 
@@ -82,8 +90,18 @@ impl TrcCtfConverter {
             sched_migrate_task_event_class: ptr::null_mut(),
             event_classes: Default::default(),
             string_cache,
-            kernel_object_map,
-            last_sched_in: None,
+            statedump: VecDeque::new(),
+            statedump_common: EventCommon {
+                number: 0,
+                ip: 0,
+                tsc: 0,
+                ctx: 0,
+                pmc1: 0,
+                pmc2: 0,
+                kclock: 0,
+                type_: 0,
+                cpu: 0,
+            },
         }
     }
 
@@ -93,7 +111,8 @@ impl TrcCtfConverter {
     ) -> Result<*mut ffi::bt_field_class, Error> {
         unsafe {
             // Create common event context
-            // event ID, event count, instruction pointer, timestamp, ctx, pmc1/2, kclock
+            // event ID, event count, instruction pointer, timestamp, ctx, pmc1/2, kclock, name,
+            // dbg_id, symbols of the instruction pointer and of the user ip of the payload
             let base_event_context = ffi::bt_field_class_structure_create(trace_class);
 
             let event_id_field = ffi::bt_field_class_integer_unsigned_create(trace_class);
@@ -188,6 +207,22 @@ impl TrcCtfConverter {
             );
             ret.capi_result()?;
 
+            let event_ip_sym_field = ffi::bt_field_class_string_create(trace_class);
+            let ret = ffi::bt_field_class_structure_append_member(
+                base_event_context,
+                c"ip_sym".as_ptr() as _,
+                event_ip_sym_field,
+            );
+            ret.capi_result()?;
+
+            let event_user_ip_sym_field = ffi::bt_field_class_string_create(trace_class);
+            let ret = ffi::bt_field_class_structure_append_member(
+                base_event_context,
+                c"user_ip_sym".as_ptr() as _,
+                event_user_ip_sym_field,
+            );
+            ret.capi_result()?;
+
             ffi::bt_field_class_put_ref(event_id_field);
             ffi::bt_field_class_put_ref(event_count_field);
             ffi::bt_field_class_put_ref(event_ip_field);
@@ -198,6 +233,8 @@ impl TrcCtfConverter {
             ffi::bt_field_class_put_ref(event_kclock_field);
             ffi::bt_field_class_put_ref(event_name_field);
             ffi::bt_field_class_put_ref(event_dbg_id_field);
+            ffi::bt_field_class_put_ref(event_ip_sym_field);
+            ffi::bt_field_class_put_ref(event_user_ip_sym_field);
 
             Ok(base_event_context)
         }
@@ -215,6 +252,7 @@ impl TrcCtfConverter {
     fn add_event_common_ctx(
         &mut self,
         common: EventCommon,
+        context: &EventContext,
         event: *mut ffi::bt_event,
     ) -> Result<(), Error> {
         let event_id = common.type_;
@@ -254,31 +292,43 @@ impl TrcCtfConverter {
                 ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, 7);
             ffi::bt_field_integer_unsigned_set_value(kclock_field, common.kclock as u64);
 
-            let map = self.kernel_object_map.borrow();
-            let kernel_object = map.get(&(common.ctx & CTX_MASK));
-
-            let (c_name_id, c_dbg_id_id) = match kernel_object {
-                Some(o) => {
-                    let id_1 = self.string_cache.insert_str(o.name())?;
-                    let id_2 = self.string_cache.insert_str(o.id())?;
-                    (id_1, id_2)
-                }
-                None => {
-                    let id_1 = self.string_cache.insert_str("")?;
-                    (id_1, id_1)
-                }
-            };
-
-            let c_name = self.string_cache.get_str_by_id(c_name_id);
-            let c_dbg_id = self.string_cache.get_str_by_id(c_dbg_id_id);
+            let dbg_id = context.dbg_id.map(|id| id.to_string()).unwrap_or_default();
+            let c_name_id = self.string_cache.insert_str(&context.name)?;
+            let c_dbg_id_id = self.string_cache.insert_str(&dbg_id)?;
+            let ip_sym_id = self
+                .string_cache
+                .insert_str(context.ip_sym.as_deref().unwrap_or_default())?;
+            let user_ip_sym_id = self
+                .string_cache
+                .insert_str(context.user_ip_sym.as_deref().unwrap_or_default())?;
 
             let name_field =
                 ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, 8);
-            ffi::bt_field_string_set_value(name_field, c_name.as_ptr());
+            ffi::bt_field_string_set_value(
+                name_field,
+                self.string_cache.get_str_by_id(c_name_id).as_ptr(),
+            );
 
             let dbg_id_field =
                 ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, 9);
-            ffi::bt_field_string_set_value(dbg_id_field, c_dbg_id.as_ptr());
+            ffi::bt_field_string_set_value(
+                dbg_id_field,
+                self.string_cache.get_str_by_id(c_dbg_id_id).as_ptr(),
+            );
+
+            let ip_sym_field =
+                ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, 10);
+            ffi::bt_field_string_set_value(
+                ip_sym_field,
+                self.string_cache.get_str_by_id(ip_sym_id).as_ptr(),
+            );
+
+            let user_ip_sym_field =
+                ffi::bt_field_structure_borrow_member_field_by_index(common_ctx_field, 11);
+            ffi::bt_field_string_set_value(
+                user_ip_sym_field,
+                self.string_cache.get_str_by_id(user_ip_sym_id).as_ptr(),
+            );
 
             Ok(())
         }
@@ -303,18 +353,176 @@ impl TrcCtfConverter {
         Ok(*event_class_ref as *const _)
     }
 
-    pub fn convert(&mut self, event: Event, ctf_state: &mut BorrowedCtfState) -> Result<(), Error> {
-        let event_type = event.to_string();
-        let event_common = event.event_common();
+    /// Queue a statedump of the kernel object model (see [`StatedumpEntry::snapshot`]), so
+    /// viewers know the names of all threads, tasks and gates from the first event on
+    pub fn begin_statedump(&mut self, entries: Vec<StatedumpEntry>, timestamp: u64, cpu: u8) {
+        self.statedump_common.tsc = timestamp;
+        self.statedump_common.cpu = cpu;
+        self.statedump = entries.into();
+    }
+
+    pub fn statedump_pending(&self) -> bool {
+        !self.statedump.is_empty()
+    }
+
+    /// Emit as many of the queued statedump events as fit into the current message batch
+    pub fn emit_statedump(&mut self, ctf_state: &mut BorrowedCtfState) -> Result<(), Error> {
+        while !ctf_state.is_full() {
+            let Some(entry) = self.statedump.pop_front() else {
+                break;
+            };
+
+            let common = self.statedump_common;
+            self.emit_statedump_entry(ctf_state, common, &EventContext::default(), entry)?;
+        }
+
+        Ok(())
+    }
+
+    // emits a single statedump event, which needs one message
+    fn emit_statedump_entry(
+        &mut self,
+        ctf_state: &mut BorrowedCtfState,
+        common: EventCommon,
+        context: &EventContext,
+        entry: StatedumpEntry,
+    ) -> Result<(), Error> {
+        match entry {
+            StatedumpEntry::Start => {
+                let (msg, ctf_event) = self.create_statedump_message(
+                    ctf_state,
+                    common,
+                    context,
+                    "lttng_statedump_start",
+                    StatedumpStart::event_class,
+                )?;
+                StatedumpStart {}.emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+            StatedumpEntry::Process {
+                tid,
+                pid,
+                name,
+                status,
+            } => {
+                let (msg, ctf_event) = self.create_statedump_message(
+                    ctf_state,
+                    common,
+                    context,
+                    "lttng_statedump_process_state",
+                    StatedumpProcessState::event_class,
+                )?;
+                StatedumpProcessState::new(
+                    tid,
+                    pid,
+                    &name,
+                    status,
+                    common.cpu,
+                    &mut self.string_cache,
+                )?
+                .emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+            StatedumpEntry::Gate {
+                addr,
+                dbg_id,
+                name,
+                thread_tid,
+                thread_name,
+            } => {
+                let (msg, ctf_event) = self.create_statedump_message(
+                    ctf_state,
+                    common,
+                    context,
+                    "l4re_statedump_ipc_gate",
+                    StatedumpIpcGate::event_class,
+                )?;
+                let name_id = self.string_cache.insert_str(&name)?;
+                let thread_name_id = self.string_cache.insert_str(&thread_name)?;
+                StatedumpIpcGate {
+                    addr,
+                    dbg_id,
+                    name: self.string_cache.get_str_by_id(name_id),
+                    thread_tid,
+                    thread_name: self.string_cache.get_str_by_id(thread_name_id),
+                }
+                .emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+            StatedumpEntry::Kobject {
+                addr,
+                dbg_id,
+                name,
+                kind,
+            } => {
+                let (msg, ctf_event) = self.create_statedump_message(
+                    ctf_state,
+                    common,
+                    context,
+                    "l4re_statedump_kobject",
+                    StatedumpKobject::event_class,
+                )?;
+                let name_id = self.string_cache.insert_str(&name)?;
+                let kind_id = self.string_cache.insert_str(kind)?;
+                StatedumpKobject {
+                    addr,
+                    dbg_id,
+                    name: self.string_cache.get_str_by_id(name_id),
+                    kind: self.string_cache.get_str_by_id(kind_id),
+                }
+                .emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+            StatedumpEntry::End => {
+                let (msg, ctf_event) = self.create_statedump_message(
+                    ctf_state,
+                    common,
+                    context,
+                    "lttng_statedump_end",
+                    StatedumpEnd::event_class,
+                )?;
+                StatedumpEnd {}.emit_event(ctf_event)?;
+                ctf_state.push_message(msg)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_statedump_message<F>(
+        &mut self,
+        ctf_state: &mut BorrowedCtfState,
+        common: EventCommon,
+        context: &EventContext,
+        event_name: &str,
+        f: F,
+    ) -> Result<(*mut ffi::bt_message, *mut ffi::bt_event), Error>
+    where
+        F: FnOnce(*mut ffi::bt_stream_class) -> Result<*mut ffi::bt_event_class, Error>,
+    {
+        let stream_class = unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
+        let event_class = self.event_class(stream_class, event_name.to_string(), f)?;
+        let msg = ctf_state.create_message(event_class, common.tsc);
+        let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+        self.add_event_common_ctx(common, context, ctf_event)?;
+        Ok((msg, ctf_event))
+    }
+
+    pub fn convert(
+        &mut self,
+        event: &SinkEvent,
+        ctf_state: &mut BorrowedCtfState,
+    ) -> Result<(), Error> {
+        let event_type = event.event.to_string();
+        let event_common = event.event.event_common();
         let event_timestamp = event_common.tsc;
 
-        match event {
+        match event.event {
             Event::Ke(ev) => {
                 let stream_class = unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
                 let event_class = self.event_class(stream_class, event_type, Ke::event_class)?;
                 let msg = ctf_state.create_message(event_class, event_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
+                self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
 
                 Ke::try_from((ev, &mut self.string_cache))?.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
@@ -324,7 +532,7 @@ impl TrcCtfConverter {
                 let event_class = self.event_class(stream_class, event_type, KeReg::event_class)?;
                 let msg = ctf_state.create_message(event_class, event_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
+                self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
 
                 KeReg::try_from((ev, &mut self.string_cache))?.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
@@ -334,91 +542,43 @@ impl TrcCtfConverter {
                 let event_class = self.event_class(stream_class, event_type, KeBin::event_class)?;
                 let msg = ctf_state.create_message(event_class, event_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
+                self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
 
                 KeBin::try_from((ev, &mut self.string_cache))?.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
             }
             Event::Nam(ev) => {
-                let name = helpers::i8_array_to_string(ev.name);
-                let name = if let Ok(n) = name {
-                    n
-                } else {
-                    // TODO not sure why, but sometimes when you enable IPC events there's some
-                    // gibberish in some name fields
-                    info!(
-                        "Could not convert Nam event bytes to name string! (event nr: {}, bytes: {:?})",
-                        ev.common.number, ev.name
-                    );
-                    "".to_string()
-                };
-
-                let pointer = if ev.thread == 0 {
-                    ev.obj & CTX_MASK
-                } else {
-                    ev.obj
-                };
-                let id = ev.id.to_string();
-                match self.kernel_object_map.borrow_mut().entry(pointer) {
-                    Entry::Occupied(mut entry) => {
-                        let obj = entry.get_mut();
-                        obj.set_id(id);
-                        obj.set_name(name);
-                    }
-
-                    Entry::Vacant(entry) => {
-                        let new_obj;
-                        let base = BaseKernelObject {
-                            id,
-                            name: name.to_string(),
-                        };
-                        if ev.thread != 0 {
-                            new_obj = KernelObject::Gate(GateObject {
-                                base,
-                                thread: ev.thread & CTX_MASK,
-                            });
-                        } else {
-                            new_obj = KernelObject::Generic(base);
-                        }
-
-                        entry.insert(new_obj);
-                    }
-                }
-
                 let stream_class = unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
                 let event_class = self.event_class(stream_class, event_type, Nam::event_class)?;
                 let msg = ctf_state.create_message(event_class, event_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
+                self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
 
                 Nam::try_from((ev, &mut self.string_cache))?.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
             }
             Event::ContextSwitch(ev) => {
-                let event_class = self.sched_switch_event_class;
-                let msg = ctf_state.create_message(event_class, event_timestamp);
-                let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
-                SchedSwitch::try_from((
-                    ev,
-                    &mut self.string_cache,
-                    &mut self.kernel_object_map,
-                    &mut self.last_sched_in,
-                ))?
-                .emit_event(ctf_event)?;
-                ctf_state.push_message(msg)?;
+                // the enricher leaves out switches of objects which aren't threads
+                for detail in &event.details {
+                    let Detail::ContextSwitch { prev, next, .. } = detail else {
+                        continue;
+                    };
+                    let event_class = self.sched_switch_event_class;
+                    let msg = ctf_state.create_message(event_class, event_timestamp);
+                    let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                    self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+                    SchedSwitch::new(&ev, prev, next, &mut self.string_cache)?
+                        .emit_event(ctf_event)?;
+                    ctf_state.push_message(msg)?;
+                }
             }
             Event::Migration(ev) => {
                 let event_class = self.sched_migrate_task_event_class;
                 let msg = ctf_state.create_message(event_class, event_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
-                SchedMigrateTask::try_from((
-                    ev,
-                    &mut self.string_cache,
-                    &mut self.kernel_object_map,
-                ))?
-                .emit_event(ctf_event)?;
+                self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+                SchedMigrateTask::new(&ev, &event.thread, &mut self.string_cache)?
+                    .emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
             }
             Event::Ipc(ev) => {
@@ -426,11 +586,39 @@ impl TrcCtfConverter {
                 let event_class = self.event_class(stream_class, event_type, Ipc::event_class)?;
                 let msg = ctf_state.create_message(event_class, event_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
-
-                Ipc::try_from((ev, &mut self.string_cache, &mut self.kernel_object_map))?
-                    .emit_event(ctf_event)?;
+                self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+
+                let (gate, callee) = event
+                    .details
+                    .iter()
+                    .find_map(|d| match d {
+                        Detail::IpcMessage { gate, callee, .. } => {
+                            Some((gate.as_str(), callee.as_str()))
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                Ipc::new(&ev, gate, callee, &mut self.string_cache)?.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
+
+                for detail in &event.details {
+                    let Detail::IpcSend { flow_id, receiver } = detail else {
+                        continue;
+                    };
+                    let stream_class =
+                        unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
+                    let event_class = self.event_class(
+                        stream_class,
+                        "l4re_ipc_flow_send".to_string(),
+                        IpcFlowSend::event_class,
+                    )?;
+                    let msg = ctf_state.create_message(event_class, event_timestamp);
+                    let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                    self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+                    IpcFlowSend::new(*flow_id, receiver, &mut self.string_cache)?
+                        .emit_event(ctf_event)?;
+                    ctf_state.push_message(msg)?;
+                }
             }
             Event::IpcRes(ev) => {
                 let stream_class = unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
@@ -438,66 +626,186 @@ impl TrcCtfConverter {
                     self.event_class(stream_class, event_type, IpcRes::event_class)?;
                 let msg = ctf_state.create_message(event_class, event_timestamp);
                 let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
-                self.add_event_common_ctx(event_common, ctf_event)?;
-                IpcRes::try_from((ev, &mut self.string_cache, &mut self.kernel_object_map))?
-                    .emit_event(ctf_event)?;
+                self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+
+                let completed = event.details.iter().find_map(|d| match d {
+                    Detail::IpcCompleted(completed) => Some(completed),
+                    _ => None,
+                });
+                IpcRes::new(&ev, completed, &mut self.string_cache)?.emit_event(ctf_event)?;
                 ctf_state.push_message(msg)?;
+
+                for detail in &event.details {
+                    let Detail::IpcReceive { flow_id, sender } = detail else {
+                        continue;
+                    };
+                    let stream_class =
+                        unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
+                    let event_class = self.event_class(
+                        stream_class,
+                        "l4re_ipc_flow_recv".to_string(),
+                        IpcFlowRecv::event_class,
+                    )?;
+                    let msg = ctf_state.create_message(event_class, event_timestamp);
+                    let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                    self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+                    IpcFlowRecv::new(*flow_id, sender, &mut self.string_cache)?
+                        .emit_event(ctf_event)?;
+                    ctf_state.push_message(msg)?;
+                }
             }
             Event::Destroy(ev) => {
-                self.kernel_object_map
-                    .borrow_mut()
-                    .remove(&(ev.obj & CTX_MASK));
-                emit_event!(event_type, DestroyEvent, self, ev, ctf_state, event_common)
+                emit_event!(event_type, DestroyEvent, self, ev, ctf_state, event);
+
+                for detail in &event.details {
+                    let Detail::ObjectDestroyed {
+                        addr,
+                        kobj_type,
+                        name,
+                        generation,
+                        created,
+                    } = detail
+                    else {
+                        continue;
+                    };
+                    let stream_class =
+                        unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
+                    let event_class = self.event_class(
+                        stream_class,
+                        "kobj_destroy".to_string(),
+                        KobjDestroy::event_class,
+                    )?;
+                    let msg = ctf_state.create_message(event_class, event_timestamp);
+                    let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                    self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+                    KobjDestroy::new(
+                        &ev,
+                        *addr,
+                        *kobj_type,
+                        name,
+                        *generation,
+                        *created,
+                        &mut self.string_cache,
+                    )?
+                    .emit_event(ctf_event)?;
+                    ctf_state.push_message(msg)?;
+                }
             }
             Event::Factory(ev) => {
-                let id = ev.newo.to_string();
-                let name = "".to_string();
-                let new_obj = KernelObject::Generic(BaseKernelObject { id, name });
-
-                self.kernel_object_map
-                    .borrow_mut()
-                    .insert(ev.obj & CTX_MASK, new_obj);
-                emit_event!(event_type, FactoryEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Pf(ev) => emit_event!(event_type, PfEvent, self, ev, ctf_state, event_common),
-            Event::Drq(ev) => emit_event!(event_type, DrqEvent, self, ev, ctf_state, event_common),
-            Event::Vcpu(ev) => {
-                emit_event!(event_type, VcpuEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Gate(ev) => {
-                emit_event!(event_type, GateEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Irq(ev) => emit_event!(event_type, IrqEvent, self, ev, ctf_state, event_common),
-            Event::Rcu(ev) => emit_event!(event_type, RcuEvent, self, ev, ctf_state, event_common),
-            Event::Tmap(ev) => {
-                emit_event!(event_type, TmapEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Bp(ev) => emit_event!(event_type, BpEvent, self, ev, ctf_state, event_common),
-            Event::Empty(ev) => {
-                emit_event!(event_type, EmptyEvent, self, ev, ctf_state, event_common)
+                emit_event!(event_type, FactoryEvent, self, ev, ctf_state, event);
+
+                for detail in &event.details {
+                    let Detail::ObjectCreated {
+                        addr,
+                        kobj_type,
+                        generation,
+                    } = detail
+                    else {
+                        continue;
+                    };
+                    let stream_class =
+                        unsafe { ffi::bt_stream_borrow_class(ctf_state.stream_mut()) };
+                    let event_class = self.event_class(
+                        stream_class,
+                        "kobj_create".to_string(),
+                        KobjCreate::event_class,
+                    )?;
+                    let msg = ctf_state.create_message(event_class, event_timestamp);
+                    let ctf_event = unsafe { ffi::bt_message_event_borrow_event(msg) };
+                    self.add_event_common_ctx(event_common, &event.context, ctf_event)?;
+                    KobjCreate::new(&ev, *addr, *kobj_type, *generation, &mut self.string_cache)?
+                        .emit_event(ctf_event)?;
+                    ctf_state.push_message(msg)?;
+                }
             }
+            Event::Pf(ev) => emit_event!(event_type, PfEvent, self, ev, ctf_state, event),
+            Event::Drq(ev) => emit_event!(event_type, DrqEvent, self, ev, ctf_state, event),
+            Event::Vcpu(ev) => emit_event!(event_type, VcpuEvent, self, ev, ctf_state, event),
+            Event::Gate(ev) => emit_event!(event_type, GateEvent, self, ev, ctf_state, event),
+            Event::Irq(ev) => emit_event!(event_type, IrqEvent, self, ev, ctf_state, event),
+            Event::Rcu(ev) => emit_event!(event_type, RcuEvent, self, ev, ctf_state, event),
+            Event::Tmap(ev) => emit_event!(event_type, TmapEvent, self, ev, ctf_state, event),
+            Event::Bp(ev) => emit_event!(event_type, BpEvent, self, ev, ctf_state, event),
+            Event::Empty(ev) => emit_event!(event_type, EmptyEvent, self, ev, ctf_state, event),
             Event::Sched(ev) => {
-                emit_event!(event_type, SchedEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Trap(ev) => {
-                emit_event!(event_type, TrapEvent, self, ev, ctf_state, event_common)
+                emit_event!(event_type, SchedEvent, self, ev, ctf_state, event);
+
+                for detail in &event.details {
+                    match *detail {
+                        Detail::SchedBudget {
+                            tid,
+                            sc_id,
+                            prio,
+                            quantum,
+                            left,
+                            expired: true,
+                        } => {
+                            let name = "l4re_sched_quantum_expired".to_string();
+                            let budget = SchedQuantumExpired {
+                                tid,
+                                sc_id,
+                                prio,
+                                quantum,
+                                left,
+                            };
+                            emit_event!(name, SchedQuantumExpired, self, budget, ctf_state, event)
+                        }
+                        Detail::SchedBudget {
+                            tid,
+                            sc_id,
+                            prio,
+                            quantum,
+                            left,
+                            expired: false,
+                        } => {
+                            let name = "l4re_sched_preempt_budget".to_string();
+                            let budget = SchedPreemptBudget {
+                                tid,
+                                sc_id,
+                                prio,
+                                quantum,
+                                left,
+                            };
+                            emit_event!(name, SchedPreemptBudget, self, budget, ctf_state, event)
+                        }
+                        Detail::SchedPrioChange {
+                            tid,
+                            sc_id,
+                            old_prio,
+                            new_prio,
+                        } => {
+                            let name = "l4re_sched_prio_change".to_string();
+                            let change = SchedPrioChange {
+                                tid,
+                                sc_id,
+                                old_prio,
+                                new_prio,
+                            };
+                            emit_event!(name, SchedPrioChange, self, change, ctf_state, event)
+                        }
+                        _ => (),
+                    }
+                }
             }
+            Event::Trap(ev) => emit_event!(event_type, TrapEvent, self, ev, ctf_state, event),
             Event::Fullsize(ev) => {
-                emit_event!(event_type, FullsizeEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Ieh(ev) => emit_event!(event_type, IehEvent, self, ev, ctf_state, event_common),
-            Event::Ipfh(ev) => {
-                emit_event!(event_type, IpfhEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Exregs(ev) => {
-                emit_event!(event_type, ExregsEvent, self, ev, ctf_state, event_common)
-            }
-            Event::Timer(ev) => {
-                emit_event!(event_type, TimerEvent, self, ev, ctf_state, event_common)
+                emit_event!(event_type, FullsizeEvent, self, ev, ctf_state, event)
             }
-            Event::Svm(ev) => emit_event!(event_type, SvmEvent, self, ev, ctf_state, event_common),
+            Event::Ieh(ev) => emit_event!(event_type, IehEvent, self, ev, ctf_state, event),
+            Event::Ipfh(ev) => emit_event!(event_type, IpfhEvent, self, ev, ctf_state, event),
+            Event::Exregs(ev) => emit_event!(event_type, ExregsEvent, self, ev, ctf_state, event),
+            Event::Timer(ev) => emit_event!(event_type, TimerEvent, self, ev, ctf_state, event),
+            Event::Svm(ev) => emit_event!(event_type, SvmEvent, self, ev, ctf_state, event),
         }
 
+        // `sched_switch` has no pid, the task of a thread is told in process states instead
+        for detail in &event.details {
+            if let Detail::TaskLinked(thread) = detail {
+                for entry in StatedumpEntry::task_link(thread) {
+                    self.emit_statedump_entry(ctf_state, event_common, &event.context, entry)?;
+                }
+            }
+        }
         Ok(())
     }
 }
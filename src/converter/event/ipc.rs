@@ -1,17 +1,11 @@
-use std::{cell::RefCell, collections::HashMap, ffi::CStr, rc::Rc};
+use std::ffi::CStr;
 
 use babeltrace2_sys::Error;
 use ctf_macros::CtfEventClass;
 
-use crate::{
-    converter::{
-        CTX_MASK,
-        kernel_object::{KernelObject, ThreadState},
-        types::StringCache,
-    },
-    event::ipc::IpcEvent,
-};
+use crate::{converter::types::StringCache, event::ipc::IpcEvent};
 
+use super::ipc_decode::{self, MsgTag};
 use super::ipc_type::IpcType;
 
 #[derive(CtfEventClass)]
@@ -28,55 +22,36 @@ pub struct Ipc<'a> {
     rcv_name: &'a CStr,
     type_: &'a CStr,
     dst_thread_name: &'a CStr,
+    tag_label: i64,
+    tag_words: u8,
+    tag_items: u8,
+    tag_flags: u8,
+    snd_timeout: &'a CStr,
+    rcv_timeout: &'a CStr,
+    snd_timeout_raw: u16,
+    rcv_timeout_raw: u16,
+    dst_cap: u64,
+    dst_cap_invalid: u8,
 }
 
-impl<'a>
-    TryFrom<(
-        IpcEvent,
-        &'a mut StringCache,
-        &'a mut Rc<RefCell<HashMap<u64, KernelObject>>>,
-    )> for Ipc<'a>
-{
-    type Error = Error;
-
-    fn try_from(
-        v: (
-            IpcEvent,
-            &'a mut StringCache,
-            &'a mut Rc<RefCell<HashMap<u64, KernelObject>>>,
-        ),
-    ) -> Result<Self, Self::Error> {
-        let (event, cache, map) = v;
-
-        if let Some(o) = map.borrow_mut().get_mut(&(event.common.ctx & CTX_MASK)) {
-            if let KernelObject::Thread(t) = o {
-                t.state = ThreadState::Blocked;
-            }
-        }
-
-        // TODO: this is slow, use an id -> name map
-        let binding = map.borrow();
-        let res = binding
-            .iter()
-            .find(|(_, o)| *o.id() == event.dbg_id.to_string());
-        let mut rcv_name = "";
-        let mut dst_thread_name = "";
-        if let Some((_, o)) = res {
-            rcv_name = o.name();
-
-            if let KernelObject::Gate(g) = o {
-                if let Some(k) = binding.get(&g.thread) {
-                    dst_thread_name = if k.name() != "" { k.name() } else { k.id() }
-                }
-            }
-        }
-
+impl<'a> Ipc<'a> {
+    /// The IPC of `event` to `gate`, which is received by the thread `callee`
+    pub fn new(
+        event: &IpcEvent,
+        gate: &str,
+        callee: &str,
+        cache: &'a mut StringCache,
+    ) -> Result<Self, Error> {
         let type_name = IpcType::num_to_str((event.dst & 0xf) as u8);
         cache.insert_str(&type_name)?;
-        cache.insert_str(rcv_name)?;
-        cache.insert_str(dst_thread_name)?;
+        cache.insert_str(gate)?;
+        cache.insert_str(callee)?;
 
-        // if let Some(dst_thread) = binding.get()
+        let tag = MsgTag::decode(event.tag);
+        let (snd_timeout_raw, rcv_timeout_raw) = ipc_decode::split_timeout(event.timeout);
+        let (snd_timeout, rcv_timeout) = ipc_decode::timeouts(event.timeout);
+        cache.insert_str(&snd_timeout)?;
+        cache.insert_str(&rcv_timeout)?;
 
         Ok(Self {
             tag: event.tag,
@@ -87,9 +62,19 @@ impl<'a>
             label: event.label,
             timeout: event.timeout,
             to_abs_rcv: event.to_abs_rcv,
-            rcv_name: cache.get_str(rcv_name),
+            rcv_name: cache.get_str(gate),
             type_: cache.get_str(&type_name),
-            dst_thread_name: cache.get_str(dst_thread_name),
+            dst_thread_name: cache.get_str(callee),
+            tag_label: tag.label,
+            tag_words: tag.words,
+            tag_items: tag.items,
+            tag_flags: tag.flags,
+            snd_timeout: cache.get_str(&snd_timeout),
+            rcv_timeout: cache.get_str(&rcv_timeout),
+            snd_timeout_raw,
+            rcv_timeout_raw,
+            dst_cap: ipc_decode::cap_index(event.dst),
+            dst_cap_invalid: ipc_decode::cap_is_invalid(event.dst) as u8,
         })
     }
 }
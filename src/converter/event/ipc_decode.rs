@@ -0,0 +1,126 @@
+//! Decoding of the raw L4 IPC values in `IPC` and `IPCRES` events
+
+// capability selectors carry the index of the capability above the syscall flags
+const CAP_SHIFT: u64 = 12;
+const INVALID_CAP_BIT: u64 = 0x800;
+
+const IPC_ERROR_MASK: u64 = 0x1f;
+const IPC_ERROR_RECV_PHASE: u64 = 0x1;
+
+const TIMEOUT_ABSOLUTE_BIT: u16 = 0x8000;
+
+/// [`MsgTag::label`] of the page fault IPCs the kernel sends to the pager of a thread
+pub const LABEL_PAGE_FAULT: i64 = -2;
+
+/// Names of the bits of [`MsgTag::flags`], from the lowest
+pub const MSGTAG_FLAGS: [&str; 4] = ["transfer_fpu", "schedule", "propagate", "error"];
+
+// the IPC error codes, starting at 1, the lowest bit of the error is the phase
+const ERROR_CODES: [&str; 8] = [
+    "timeout",
+    "not_existent",
+    "canceled",
+    "map_failed",
+    "snd_pf_timeout",
+    "rcv_pf_timeout",
+    "aborted",
+    "msg_cut",
+];
+
+/// The raw timeouts with a name instead of a duration, as inclusive ranges
+pub const SPECIAL_TIMEOUTS: [(&str, u16, u16); 2] = [
+    ("never", 0, 0),
+    ("absolute", TIMEOUT_ABSOLUTE_BIT, u16::MAX),
+];
+
+/// The fields of an `l4_msgtag_t`
+#[derive(Debug, Clone, Copy)]
+pub struct MsgTag {
+    /// Protocol / label, negative for kernel protocols
+    pub label: i64,
+    pub words: u8,
+    pub items: u8,
+    pub flags: u8,
+}
+
+impl MsgTag {
+    pub fn decode(raw: u64) -> Self {
+        Self {
+            label: (raw as i64) >> 16,
+            words: (raw & 0x3f) as u8,
+            items: ((raw >> 6) & 0x3f) as u8,
+            flags: ((raw >> 12) & 0xf) as u8,
+        }
+    }
+}
+
+/// Capability index of the destination selector of an `IPC` event
+pub fn cap_index(dst: u64) -> u64 {
+    dst >> CAP_SHIFT
+}
+
+/// True if the destination selector has the invalid capability bit set (e.g. for a pure wait)
+pub fn cap_is_invalid(dst: u64) -> bool {
+    dst & INVALID_CAP_BIT != 0
+}
+
+/// The IPC error in the `result` of an `IPCRES` event, 0 without an error
+pub fn error_code(result: u64) -> u8 {
+    (result & IPC_ERROR_MASK) as u8
+}
+
+/// Name of the IPC error in the `result` of an `IPCRES` event, e.g. `rcv_timeout`
+pub fn error_str(result: u64) -> String {
+    error_name(error_code(result))
+}
+
+/// The known error codes of [`error_code`] with their names
+pub fn errors() -> impl Iterator<Item = (u8, String)> {
+    let last = ((ERROR_CODES.len() as u8) << 1) | IPC_ERROR_RECV_PHASE as u8;
+    std::iter::once(0)
+        .chain(2..=last)
+        .map(|error| (error, error_name(error)))
+}
+
+fn error_name(error: u8) -> String {
+    if error == 0 {
+        return "ok".to_string();
+    }
+
+    let phase = if error as u64 & IPC_ERROR_RECV_PHASE != 0 {
+        "rcv"
+    } else {
+        "snd"
+    };
+    let code = ((error >> 1) as usize)
+        .checked_sub(1)
+        .and_then(|i| ERROR_CODES.get(i))
+        .unwrap_or(&"unknown");
+    format!("{phase}_{code}")
+}
+
+/// The raw send and receive timeout of the `timeout` field of an `IPC` event
+pub fn split_timeout(timeout: u32) -> (u16, u16) {
+    ((timeout >> 16) as u16, (timeout & 0xffff) as u16)
+}
+
+/// Send and receive timeout of the `timeout` field of an `IPC` event
+pub fn timeouts(timeout: u32) -> (String, String) {
+    let (snd, rcv) = split_timeout(timeout);
+    (timeout_str(snd), timeout_str(rcv))
+}
+
+// a relative timeout is mantissa * 2^exponent microseconds, all zero meaning infinite
+fn timeout_str(timeout: u16) -> String {
+    if timeout & TIMEOUT_ABSOLUTE_BIT != 0 {
+        return "absolute".to_string();
+    }
+
+    let mantissa = (timeout & 0x3ff) as u64;
+    let exponent = ((timeout >> 10) & 0x1f) as u32;
+    if mantissa == 0 && exponent == 0 {
+        "never".to_string()
+    } else {
+        format!("{}us", mantissa << exponent)
+    }
+}
@@ -0,0 +1,57 @@
+use std::ffi::CStr;
+
+use babeltrace2_sys::Error;
+use ctf_macros::CtfEventClass;
+
+use crate::converter::types::StringCache;
+use crate::sink::ThreadInfo;
+
+/// Send end of an IPC flow, emitted by the sending thread
+#[derive(CtfEventClass)]
+#[event_name = "l4re_ipc_flow_send"]
+pub struct IpcFlowSend<'a> {
+    pub flow_id: u64,
+    pub dst_tid: i64,
+    pub dst_name: &'a CStr,
+}
+
+/// Receive end of an IPC flow, emitted by the receiving thread with the flow id of the send end
+#[derive(CtfEventClass)]
+#[event_name = "l4re_ipc_flow_recv"]
+pub struct IpcFlowRecv<'a> {
+    pub flow_id: u64,
+    pub src_tid: i64,
+    pub src_name: &'a CStr,
+}
+
+impl<'a> IpcFlowSend<'a> {
+    pub fn new(
+        flow_id: u64,
+        receiver: &ThreadInfo,
+        cache: &'a mut StringCache,
+    ) -> Result<Self, Error> {
+        let name_id = cache.insert_str(&receiver.name)?;
+
+        Ok(Self {
+            flow_id,
+            dst_tid: receiver.tid,
+            dst_name: cache.get_str_by_id(name_id),
+        })
+    }
+}
+
+impl<'a> IpcFlowRecv<'a> {
+    pub fn new(
+        flow_id: u64,
+        sender: &ThreadInfo,
+        cache: &'a mut StringCache,
+    ) -> Result<Self, Error> {
+        let name_id = cache.insert_str(&sender.name)?;
+
+        Ok(Self {
+            flow_id,
+            src_tid: sender.tid,
+            src_name: cache.get_str_by_id(name_id),
+        })
+    }
+}
@@ -1,17 +1,13 @@
-use std::{cell::RefCell, collections::HashMap, ffi::CStr, rc::Rc};
+use std::ffi::CStr;
 
 use babeltrace2_sys::Error;
 use ctf_macros::CtfEventClass;
 
 use crate::{
-    converter::{
-        CTX_MASK,
-        kernel_object::{KernelObject, ThreadState},
-        types::StringCache,
-    },
-    event::ipc_res::IpcResEvent,
+    analysis::ipc_latency::CompletedIpc, converter::types::StringCache, event::ipc_res::IpcResEvent,
 };
 
+use super::ipc_decode::{self, MsgTag};
 use super::ipc_type::IpcType;
 
 #[derive(CtfEventClass)]
@@ -27,35 +23,46 @@ pub struct IpcRes<'a> {
     dst: u64,
     pair_event: u64,
     type_: &'a CStr,
+    tag_label: i64,
+    tag_words: u8,
+    tag_items: u8,
+    tag_flags: u8,
+    error: &'a CStr,
+    error_code: u8,
+    // the matching IPC event, if it was seen
+    matched: u8,
+    caller: &'a CStr,
+    gate_name: &'a CStr,
+    callee_name: &'a CStr,
+    latency: u64,
 }
 
-impl<'a>
-    TryFrom<(
-        IpcResEvent,
-        &'a mut StringCache,
-        &'a mut Rc<RefCell<HashMap<u64, KernelObject>>>,
-    )> for IpcRes<'a>
-{
-    type Error = Error;
-
-    fn try_from(
-        v: (
-            IpcResEvent,
-            &'a mut StringCache,
-            &'a mut Rc<RefCell<HashMap<u64, KernelObject>>>,
-        ),
-    ) -> Result<Self, Self::Error> {
-        let (event, cache, map) = v;
-
-        if let Some(o) = map.borrow_mut().get_mut(&(event.common.ctx & CTX_MASK)) {
-            if let KernelObject::Thread(t) = o {
-                t.state = ThreadState::Running;
-            }
-        }
-
+impl<'a> IpcRes<'a> {
+    /// The result of `event`, `completed` is its IPC if it was seen
+    pub fn new(
+        event: &IpcResEvent,
+        completed: Option<&CompletedIpc>,
+        cache: &'a mut StringCache,
+    ) -> Result<Self, Error> {
         let type_name = IpcType::num_to_str((event.dst & 0xf) as u8);
         cache.insert_str(&type_name)?;
-        let type_ = cache.get_str(&type_name);
+        let error = ipc_decode::error_str(event.result);
+        cache.insert_str(&error)?;
+        let tag = MsgTag::decode(event.tag);
+
+        let matched = completed.is_some();
+        let unmatched = CompletedIpc {
+            caller: String::new(),
+            caller_task: String::new(),
+            gate: String::new(),
+            callee: String::new(),
+            latency: 0,
+            latency_ns: 0,
+        };
+        let completed = completed.unwrap_or(&unmatched);
+        cache.insert_str(&completed.caller)?;
+        cache.insert_str(&completed.gate)?;
+        cache.insert_str(&completed.callee)?;
 
         Ok(Self {
             have_snd: event.have_snd,
@@ -67,7 +74,18 @@ impl<'a>
             from: event.from,
             dst: event.dst,
             pair_event: event.pair_event,
-            type_,
+            type_: cache.get_str(&type_name),
+            tag_label: tag.label,
+            tag_words: tag.words,
+            tag_items: tag.items,
+            tag_flags: tag.flags,
+            error: cache.get_str(&error),
+            error_code: ipc_decode::error_code(event.result),
+            matched: matched as u8,
+            caller: cache.get_str(&completed.caller),
+            gate_name: cache.get_str(&completed.gate),
+            callee_name: cache.get_str(&completed.callee),
+            latency: completed.latency,
         })
     }
 }
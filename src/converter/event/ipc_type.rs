@@ -1,3 +1,4 @@
+use crate::converter::kernel_object::ThreadState;
 use log::error;
 use num_enum::TryFromPrimitive;
 
@@ -33,4 +34,31 @@ impl IpcType {
 
         format!("{:?}", type_var).to_string()
     }
+
+    /// True if the operation has a send phase, a plain call (no flags) sends and receives
+    pub fn has_send(type_number: u8) -> bool {
+        type_number == IpcType::Call as u8 || type_number & IpcType::Send as u8 != 0
+    }
+
+    pub fn has_recv(type_number: u8) -> bool {
+        type_number == IpcType::Call as u8 || type_number & IpcType::Recv as u8 != 0
+    }
+
+    /// True if the send phase goes to the caller the thread got its last message from
+    pub fn is_reply(type_number: u8) -> bool {
+        type_number & IpcType::Reply as u8 != 0
+    }
+
+    /// The state a thread waits in after starting an IPC with the given operation flags. Anything
+    /// with a receive phase (including calls) ends up waiting for a message, so only pure sends
+    /// block in the send phase.
+    pub fn blocking_state(type_number: u8) -> ThreadState {
+        let send = type_number & IpcType::Send as u8 != 0;
+        let recv = type_number & IpcType::Recv as u8 != 0;
+        if send && !recv {
+            ThreadState::IpcSendWait
+        } else {
+            ThreadState::IpcRecvWait
+        }
+    }
 }
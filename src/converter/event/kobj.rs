@@ -0,0 +1,76 @@
+use std::ffi::CStr;
+
+use babeltrace2_sys::Error;
+use ctf_macros::CtfEventClass;
+
+use crate::{
+    converter::{
+        kernel_object::{KobjAddr, KobjType},
+        types::StringCache,
+    },
+    event::{destroy::DestroyEvent, factory::FactoryEvent},
+};
+
+#[derive(CtfEventClass)]
+#[event_name = "kobj_create"]
+pub struct KobjCreate<'a> {
+    pub addr: u64,
+    pub dbg_id: i64,
+    pub type_: &'a CStr,
+    pub generation: u32,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "kobj_destroy"]
+pub struct KobjDestroy<'a> {
+    pub addr: u64,
+    pub dbg_id: i64,
+    pub name: &'a CStr,
+    pub type_: &'a CStr,
+    pub generation: u32,
+    // timestamp of the creation, 0 if the object was created before tracing started
+    pub created: u64,
+}
+
+impl<'a> KobjCreate<'a> {
+    pub fn new(
+        event: &FactoryEvent,
+        addr: KobjAddr,
+        kobj_type: KobjType,
+        generation: u32,
+        cache: &'a mut StringCache,
+    ) -> Result<Self, Error> {
+        let type_id = cache.insert_str(kobj_type.as_str())?;
+
+        Ok(Self {
+            addr: addr.as_u64(),
+            dbg_id: event.newo as i64,
+            type_: cache.get_str_by_id(type_id),
+            generation,
+        })
+    }
+}
+
+impl<'a> KobjDestroy<'a> {
+    pub fn new(
+        event: &DestroyEvent,
+        addr: KobjAddr,
+        kobj_type: KobjType,
+        name: &str,
+        generation: u32,
+        created: Option<u64>,
+        cache: &'a mut StringCache,
+    ) -> Result<Self, Error> {
+        let name_id = cache.insert_str(name)?;
+        let type_id = cache.insert_str(kobj_type.as_str())?;
+
+        Ok(Self {
+            addr: addr.as_u64(),
+            dbg_id: event.id as i64,
+            name: cache.get_str_by_id(name_id),
+            type_: cache.get_str_by_id(type_id),
+            generation,
+            created: created.unwrap_or(0),
+        })
+    }
+}
@@ -1,10 +1,15 @@
 pub mod ipc;
+pub mod ipc_decode;
+pub mod ipc_flow;
 pub mod ipc_res;
 pub mod ipc_type;
 pub mod ke;
 pub mod ke_bin;
 pub mod ke_reg;
+pub mod kobj;
 pub mod nam;
+pub mod sched_context;
 pub mod sched_migrate_task;
 pub mod sched_switch;
+pub mod statedump;
 pub mod unsupported;
@@ -0,0 +1,32 @@
+use ctf_macros::CtfEventClass;
+
+/// A scheduling context got saved without budget left
+#[derive(CtfEventClass)]
+#[event_name = "l4re_sched_quantum_expired"]
+pub struct SchedQuantumExpired {
+    pub tid: i64,
+    pub sc_id: u16,
+    pub prio: u16,
+    pub quantum: u64,
+    pub left: i64,
+}
+
+/// A scheduling context got saved with budget left, the thread was preempted or blocked
+#[derive(CtfEventClass)]
+#[event_name = "l4re_sched_preempt_budget"]
+pub struct SchedPreemptBudget {
+    pub tid: i64,
+    pub sc_id: u16,
+    pub prio: u16,
+    pub quantum: u64,
+    pub left: i64,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "l4re_sched_prio_change"]
+pub struct SchedPrioChange {
+    pub tid: i64,
+    pub sc_id: u16,
+    pub old_prio: u16,
+    pub new_prio: u16,
+}
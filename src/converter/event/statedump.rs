@@ -0,0 +1,202 @@
+use std::ffi::CStr;
+
+use babeltrace2_sys::Error;
+use ctf_macros::CtfEventClass;
+
+use crate::converter::{
+    kernel_object::{KernelObject, KernelObjectMap, KobjAddr, ThreadState, UNKNOWN_TID},
+    types::StringCache,
+};
+use crate::sink::ThreadInfo;
+
+// process status values of lttng_statedump_process_state, as interpreted by Trace Compass
+const LTTNG_UNNAMED: i32 = 0;
+const LTTNG_WAIT_CPU: i32 = 2;
+const LTTNG_WAIT: i32 = 5;
+const LTTNG_RUN: i32 = 6;
+const LTTNG_DEAD: i32 = 7;
+
+#[derive(CtfEventClass)]
+#[event_name = "lttng_statedump_start"]
+pub struct StatedumpStart {}
+
+#[derive(CtfEventClass)]
+#[event_name = "lttng_statedump_end"]
+pub struct StatedumpEnd {}
+
+#[derive(CtfEventClass)]
+#[event_name = "lttng_statedump_process_state"]
+pub struct StatedumpProcessState<'a> {
+    pub tid: i64,
+    pub vtid: i64,
+    pub pid: i64,
+    pub vpid: i64,
+    pub ppid: i64,
+    pub vppid: i64,
+    pub name: &'a CStr,
+    pub status: i32,
+    pub cpu: i32,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "l4re_statedump_ipc_gate"]
+pub struct StatedumpIpcGate<'a> {
+    pub addr: u64,
+    pub dbg_id: i64,
+    pub name: &'a CStr,
+    pub thread_tid: i64,
+    pub thread_name: &'a CStr,
+}
+
+#[derive(CtfEventClass)]
+#[event_name = "l4re_statedump_kobject"]
+pub struct StatedumpKobject<'a> {
+    pub addr: u64,
+    pub dbg_id: i64,
+    pub name: &'a CStr,
+    pub kind: &'a CStr,
+}
+
+/// Snapshot of a single kernel object, taken when the statedump starts so the emission can be
+/// spread over several message batches without holding a borrow of the kernel object map
+#[derive(Debug, Clone)]
+pub enum StatedumpEntry {
+    Start,
+    Process {
+        tid: i64,
+        pid: i64,
+        name: String,
+        status: i32,
+    },
+    Gate {
+        addr: u64,
+        dbg_id: i64,
+        name: String,
+        thread_tid: i64,
+        thread_name: String,
+    },
+    Kobject {
+        addr: u64,
+        dbg_id: i64,
+        name: String,
+        kind: &'static str,
+    },
+    End,
+}
+
+impl StatedumpEntry {
+    /// Creates the statedump entries (including start and end markers) for all objects in the map
+    pub fn snapshot(map: &KernelObjectMap) -> Vec<StatedumpEntry> {
+        let mut entries = vec![StatedumpEntry::Start];
+
+        for (addr, obj) in map.iter().filter(|(_, o)| !o.is_destroyed()) {
+            entries.push(StatedumpEntry::object(map, *addr, obj));
+        }
+
+        entries.push(StatedumpEntry::End);
+        entries
+    }
+
+    /// The process states of `thread` and of its task, to report the task of the thread once it
+    /// becomes known. Trace Compass takes the name of a process from the entry of the task.
+    pub fn task_link(thread: &ThreadInfo) -> [StatedumpEntry; 2] {
+        [
+            StatedumpEntry::Process {
+                tid: thread.pid,
+                pid: thread.pid,
+                name: thread.task.clone(),
+                status: LTTNG_UNNAMED,
+            },
+            StatedumpEntry::Process {
+                tid: thread.tid,
+                pid: thread.pid,
+                name: thread.name.clone(),
+                status: thread.state.map_or(LTTNG_WAIT_CPU, process_status),
+            },
+        ]
+    }
+
+    fn object(map: &KernelObjectMap, addr: KobjAddr, obj: &KernelObject) -> StatedumpEntry {
+        let addr = addr.as_u64();
+        let dbg_id = obj.tid();
+        match obj {
+            KernelObject::Thread(t) => StatedumpEntry::Process {
+                tid: dbg_id,
+                pid: map.task_pid(t.task, dbg_id).unwrap_or(dbg_id),
+                name: display_name(obj),
+                status: process_status(t.state),
+            },
+            // the task itself is dumped as the "main thread" of its process, which is where
+            // Trace Compass takes the process name from
+            KernelObject::Task(_) => StatedumpEntry::Process {
+                tid: dbg_id,
+                pid: dbg_id,
+                name: display_name(obj),
+                status: LTTNG_UNNAMED,
+            },
+            KernelObject::Gate(g) => {
+                let (thread_tid, thread_name) = match map.get(&g.thread) {
+                    Some(t) => (t.tid(), display_name(t)),
+                    None => (UNKNOWN_TID, "".to_string()),
+                };
+                StatedumpEntry::Gate {
+                    addr,
+                    dbg_id,
+                    name: obj.name().to_string(),
+                    thread_tid,
+                    thread_name,
+                }
+            }
+            KernelObject::Generic(_) => StatedumpEntry::Kobject {
+                addr,
+                dbg_id,
+                name: obj.name().to_string(),
+                kind: obj.kind(),
+            },
+        }
+    }
+}
+
+fn display_name(obj: &KernelObject) -> String {
+    if obj.name().is_empty() {
+        obj.id().map(|id| id.to_string()).unwrap_or_default()
+    } else {
+        obj.name().to_string()
+    }
+}
+
+fn process_status(state: ThreadState) -> i32 {
+    match state {
+        ThreadState::Running => LTTNG_RUN,
+        ThreadState::Runnable | ThreadState::Idle => LTTNG_WAIT_CPU,
+        ThreadState::IpcSendWait | ThreadState::IpcRecvWait | ThreadState::PageFaultWait => {
+            LTTNG_WAIT
+        }
+        ThreadState::Dead => LTTNG_DEAD,
+    }
+}
+
+impl<'a> StatedumpProcessState<'a> {
+    pub fn new(
+        tid: i64,
+        pid: i64,
+        name: &str,
+        status: i32,
+        cpu: u8,
+        cache: &'a mut StringCache,
+    ) -> Result<Self, Error> {
+        let name_id = cache.insert_str(name)?;
+
+        Ok(Self {
+            tid,
+            vtid: tid,
+            pid,
+            vpid: pid,
+            ppid: 0,
+            vppid: 0,
+            name: cache.get_str_by_id(name_id),
+            status,
+            cpu: cpu as i32,
+        })
+    }
+}
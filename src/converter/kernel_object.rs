@@ -1,7 +1,171 @@
-#[derive(Debug, Clone)]
+use super::CTX_MASK;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Debug id of a kernel object, as assigned by the kernel debugger (JDB)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DbgId(pub u64);
+
+impl fmt::Display for DbgId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Thread id reported for threads which aren't known to the converter (yet)
+pub const UNKNOWN_TID: i64 = -1;
+
+/// Kernel address of an object, used as the key of the kernel object map
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KobjAddr(u64);
+
+impl KobjAddr {
+    /// Address of the object a pointer points into. Context pointers (`EventCommon::ctx`) point
+    /// somewhere into the thread's kernel stack, so they get masked with `CTX_MASK` to get the
+    /// thread object itself.
+    pub fn masked(ptr: u64) -> Self {
+        KobjAddr(ptr & CTX_MASK)
+    }
+
+    /// Address which is used as is, e.g. the object pointer of IPC gates in `NAM` events
+    pub fn exact(ptr: u64) -> Self {
+        KobjAddr(ptr)
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for KobjAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Object type, as requested from the factory when the object got created
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KobjType {
+    Generic,
+    Gate,
+    Irq,
+    Task,
+    Thread,
+    Scheduler,
+    Factory,
+    Vm,
+    DmaSpace,
+    IrqSender,
+    Semaphore,
+    Iommu,
+    VcpuContext,
+}
+
+impl KobjType {
+    /// Decodes the operation (protocol label) of a `FACTORY` event
+    pub fn from_factory_op(op: i64) -> Self {
+        match op {
+            0 => KobjType::Gate,
+            -1 => KobjType::Irq,
+            -11 => KobjType::Task,
+            -12 => KobjType::Thread,
+            -14 => KobjType::Scheduler,
+            -15 => KobjType::Factory,
+            -16 => KobjType::Vm,
+            -17 => KobjType::DmaSpace,
+            -18 => KobjType::IrqSender,
+            -20 => KobjType::Semaphore,
+            -22 => KobjType::Iommu,
+            -25 => KobjType::VcpuContext,
+            _ => KobjType::Generic,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KobjType::Generic => "generic",
+            KobjType::Gate => "gate",
+            KobjType::Irq => "irq",
+            KobjType::Task => "task",
+            KobjType::Thread => "thread",
+            KobjType::Scheduler => "scheduler",
+            KobjType::Factory => "factory",
+            KobjType::Vm => "vm",
+            KobjType::DmaSpace => "dma_space",
+            KobjType::IrqSender => "irq_sender",
+            KobjType::Semaphore => "semaphore",
+            KobjType::Iommu => "iommu",
+            KobjType::VcpuContext => "vcpu_context",
+        }
+    }
+}
+
+impl FromStr for KobjType {
+    type Err = String;
+
+    /// Parses the names of [`KobjType::as_str`], as well as the type names used by JDB
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let kobj_type = match s.to_ascii_lowercase().as_str() {
+            "generic" | "kobject" => KobjType::Generic,
+            "gate" | "ipc_gate" => KobjType::Gate,
+            "irq" => KobjType::Irq,
+            "task" => KobjType::Task,
+            "thread" => KobjType::Thread,
+            "scheduler" | "sched" => KobjType::Scheduler,
+            "factory" => KobjType::Factory,
+            "vm" => KobjType::Vm,
+            "dma_space" | "dmaspace" => KobjType::DmaSpace,
+            "irq_sender" | "irqsender" => KobjType::IrqSender,
+            "semaphore" | "irq_semaphore" => KobjType::Semaphore,
+            "iommu" => KobjType::Iommu,
+            "vcpu_context" | "vcpu" => KobjType::VcpuContext,
+            _ => return Err(format!("Unknown kernel object type '{s}'")),
+        };
+        Ok(kobj_type)
+    }
+}
+
+/// Information about an object known before the conversion starts, e.g. from a `--names` file.
+/// Presets with an address create the object right away, presets with only a debug id are applied
+/// once an object with that id shows up.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectPreset {
+    pub id: Option<DbgId>,
+    pub addr: Option<KobjAddr>,
+    pub name: String,
+    pub kobj_type: Option<KobjType>,
+    /// Debug id of the task a thread belongs to
+    pub task: Option<DbgId>,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct BaseKernelObject {
-    pub id: String,
+    /// `None` for objects preset by address, until an event tells their debug id
+    pub id: Option<DbgId>,
     pub name: String,
+    /// Type the object was created with, if it was seen being created by a factory
+    pub kobj_type: Option<KobjType>,
+    /// Timestamps of the `FACTORY` and `DESTROY` events of the object
+    pub created: Option<u64>,
+    pub destroyed: Option<u64>,
+    /// Number of objects which lived at the same address before this one
+    pub generation: u32,
+}
+
+impl BaseKernelObject {
+    pub fn new(id: DbgId, name: String) -> Self {
+        Self {
+            id: Some(id),
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// The debug id as thread id (or pid) of the trace, [`UNKNOWN_TID`] if it isn't known
+    pub fn tid(&self) -> i64 {
+        self.id.map_or(UNKNOWN_TID, |id| id.0 as i64)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -9,46 +173,73 @@ pub enum KernelObject {
     Generic(BaseKernelObject),
     Thread(ThreadObject),
     Gate(GateObject),
+    Task(TaskObject),
 }
 
 impl KernelObject {
-    pub fn id(&self) -> &str {
-        match self {
-            KernelObject::Generic(obj) => &obj.id,
-            KernelObject::Thread(obj) => &obj.base.id,
-            KernelObject::Gate(obj) => &obj.base.id,
-        }
+    pub fn id(&self) -> Option<DbgId> {
+        self.base().id
+    }
+
+    /// See [`BaseKernelObject::tid`]
+    pub fn tid(&self) -> i64 {
+        self.base().tid()
     }
 
     pub fn name(&self) -> &str {
+        &self.base().name
+    }
+
+    pub fn base(&self) -> &BaseKernelObject {
         match self {
-            KernelObject::Generic(obj) => &obj.name,
-            KernelObject::Thread(obj) => &obj.base.name,
-            KernelObject::Gate(obj) => &obj.base.name,
+            KernelObject::Generic(obj) => obj,
+            KernelObject::Thread(obj) => &obj.base,
+            KernelObject::Gate(obj) => &obj.base,
+            KernelObject::Task(obj) => &obj.base,
         }
     }
 
-    pub fn set_id(&mut self, id: String) {
+    fn base_mut(&mut self) -> &mut BaseKernelObject {
         match self {
-            KernelObject::Generic(obj) => obj.id = id,
-            KernelObject::Thread(obj) => obj.base.id = id,
-            KernelObject::Gate(obj) => obj.base.id = id,
+            KernelObject::Generic(obj) => obj,
+            KernelObject::Thread(obj) => &mut obj.base,
+            KernelObject::Gate(obj) => &mut obj.base,
+            KernelObject::Task(obj) => &mut obj.base,
         }
     }
 
-    pub fn set_name(&mut self, name: String) {
+    pub fn kobj_type(&self) -> KobjType {
         match self {
-            KernelObject::Generic(obj) => obj.name = name.into(),
-            KernelObject::Thread(obj) => obj.base.name = name.into(),
-            KernelObject::Gate(obj) => obj.base.name = name.into(),
+            KernelObject::Generic(obj) => obj.kobj_type.unwrap_or(KobjType::Generic),
+            KernelObject::Thread(_) => KobjType::Thread,
+            KernelObject::Gate(_) => KobjType::Gate,
+            KernelObject::Task(_) => KobjType::Task,
         }
     }
+
+    /// Human readable object type
+    pub fn kind(&self) -> &'static str {
+        self.kobj_type().as_str()
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.base().destroyed.is_some()
+    }
+
+    // only to be used by the map, which keeps the debug id index up to date
+    fn set_id(&mut self, id: DbgId) {
+        self.base_mut().id = Some(id);
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.base_mut().name = name;
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct GateObject {
     pub base: BaseKernelObject,
-    pub thread: u64,
+    pub thread: KobjAddr,
 }
 
 #[derive(Debug, Clone)]
@@ -56,10 +247,319 @@ pub struct ThreadObject {
     pub base: BaseKernelObject,
     pub state: ThreadState,
     pub prio: u64,
+    /// Address of the task (address space) the thread last ran in
+    pub task: Option<KobjAddr>,
+    /// Id of the scheduling context last loaded for the thread
+    pub sched_context: Option<u16>,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Debug, Clone)]
+pub struct TaskObject {
+    pub base: BaseKernelObject,
+}
+
+/// A scheduling context as seen in `SCHED` events, identified by the thread owning it and its id.
+/// Budgets are in the unit of the kernel's scheduler (microseconds).
+#[derive(Debug, Clone, Default)]
+pub struct SchedContext {
+    pub prio: u16,
+    pub quantum: u64,
+    /// Budget left at the last load or save
+    pub left: i64,
+    /// Number of `SCHED` events of the context
+    pub events: u64,
+    pub loads: u64,
+    /// Saves with budget left, i.e. the thread was preempted or blocked
+    pub preemptions: u64,
+    /// Saves without budget left
+    pub exhaustions: u64,
+    /// Smallest budget left at a preemption
+    pub min_left: Option<i64>,
+    pub prio_changes: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ThreadState {
+    /// Currently executing on a CPU
     Running,
-    Blocked,
+    /// Ready to run, but got preempted or was never scheduled so far
+    Runnable,
+    /// Blocked in the send phase of an IPC
+    IpcSendWait,
+    /// Blocked in the receive phase of an IPC (includes waiting for the reply of a call)
+    IpcRecvWait,
+    /// Waiting for its pager to resolve a page fault
+    PageFaultWait,
+    /// Destroyed by a `DESTROY` event
+    Dead,
+    /// Idle thread of a CPU
+    Idle,
+}
+
+impl ThreadState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThreadState::Running => "running",
+            ThreadState::Runnable => "runnable",
+            ThreadState::IpcSendWait => "ipc_send_wait",
+            ThreadState::IpcRecvWait => "ipc_recv_wait",
+            ThreadState::PageFaultWait => "page_fault_wait",
+            ThreadState::Dead => "dead",
+            ThreadState::Idle => "idle",
+        }
+    }
+}
+
+/// All kernel objects known to the converter, indexed by their kernel address and by their debug
+/// id. Destroyed objects stay in the map (so late events still resolve their names) until their
+/// address gets reused by a new object.
+#[derive(Debug, Default)]
+pub struct KernelObjectMap {
+    objects: HashMap<KobjAddr, KernelObject>,
+    by_dbg_id: HashMap<DbgId, KobjAddr>,
+    // `DestroyEvent::type_` is a pointer to the kernel's type info of the object, learn which
+    // type it stands for from objects of known type
+    destroy_types: HashMap<u64, KobjType>,
+    presets: HashMap<DbgId, ObjectPreset>,
+    sched_contexts: HashMap<(KobjAddr, u16), SchedContext>,
+}
+
+impl KernelObjectMap {
+    /// Adds information known before the conversion starts to the map
+    pub fn preset(&mut self, preset: ObjectPreset) {
+        if let Some(addr) = preset.addr {
+            let base = BaseKernelObject {
+                id: preset.id,
+                name: preset.name.clone(),
+                kobj_type: preset.kobj_type,
+                ..Default::default()
+            };
+            let obj = if preset.kobj_type == Some(KobjType::Task) {
+                KernelObject::Task(TaskObject { base })
+            } else {
+                // threads become thread objects with their first sched_switch, which also
+                // learns their priority
+                KernelObject::Generic(base)
+            };
+            // without a debug id the object only gets indexed once an event tells its id
+            self.insert(addr, obj);
+        }
+
+        if let Some(id) = preset.id {
+            self.presets.insert(id, preset);
+        }
+    }
+
+    // fills in what an object doesn't know about itself from the preset with its debug id
+    fn apply_preset(&mut self, addr: KobjAddr) {
+        let Some(obj) = self.objects.get_mut(&addr) else {
+            return;
+        };
+        let Some(preset) = obj.id().and_then(|id| self.presets.get(&id)) else {
+            return;
+        };
+
+        if obj.name().is_empty() {
+            obj.set_name(preset.name.clone());
+        }
+        if let KernelObject::Generic(base) = obj
+            && base.kobj_type.is_none()
+        {
+            base.kobj_type = preset.kobj_type;
+        }
+    }
+
+    pub fn get(&self, addr: &KobjAddr) -> Option<&KernelObject> {
+        self.objects.get(addr)
+    }
+
+    /// Mutable access to an object. The debug id must only be changed with [`Self::set_id`] to
+    /// keep the index consistent.
+    pub fn get_mut(&mut self, addr: &KobjAddr) -> Option<&mut KernelObject> {
+        self.objects.get_mut(addr)
+    }
+
+    /// Looks up an object by its debug id, returning its address and the object
+    pub fn get_by_dbg_id(&self, dbg_id: DbgId) -> Option<(KobjAddr, &KernelObject)> {
+        let addr = self.by_dbg_id.get(&dbg_id)?;
+        self.objects.get(addr).map(|o| (*addr, o))
+    }
+
+    /// Inserts a new object, replacing a previous object at the same address
+    pub fn insert(&mut self, addr: KobjAddr, mut obj: KernelObject) {
+        if let Some(old) = self.remove(&addr) {
+            obj.base_mut().generation = old.base().generation + 1;
+        }
+        if let Some(id) = obj.id() {
+            self.by_dbg_id.insert(id, addr);
+        }
+        self.objects.insert(addr, obj);
+        self.apply_preset(addr);
+    }
+
+    /// Marks the object at `addr` as destroyed and returns its type. If the type isn't known from
+    /// the object itself it is derived from the `type_info` pointer of the `DESTROY` event.
+    pub fn destroy(&mut self, addr: KobjAddr, type_info: u64, timestamp: u64) -> KobjType {
+        let mut kobj_type = KobjType::Generic;
+        if let Some(obj) = self.objects.get_mut(&addr) {
+            obj.base_mut().destroyed = Some(timestamp);
+            if let KernelObject::Thread(t) = obj {
+                t.state = ThreadState::Dead;
+            }
+            kobj_type = obj.kobj_type();
+        }
+
+        if kobj_type != KobjType::Generic {
+            self.destroy_types.insert(type_info, kobj_type);
+            kobj_type
+        } else {
+            self.destroy_types
+                .get(&type_info)
+                .copied()
+                .unwrap_or(KobjType::Generic)
+        }
+    }
+
+    pub fn remove(&mut self, addr: &KobjAddr) -> Option<KernelObject> {
+        let obj = self.objects.remove(addr)?;
+        if let Some(id) = obj.id() {
+            self.unindex(*addr, id);
+        }
+        Some(obj)
+    }
+
+    /// Changes the debug id of the object at `addr`
+    pub fn set_id(&mut self, addr: KobjAddr, dbg_id: DbgId) {
+        let Some(obj) = self.objects.get_mut(&addr) else {
+            return;
+        };
+        let old_id = obj.id();
+        obj.set_id(dbg_id);
+
+        if let Some(old_id) = old_id {
+            self.unindex(addr, old_id);
+        }
+        self.by_dbg_id.insert(dbg_id, addr);
+        self.apply_preset(addr);
+    }
+
+    /// Marks the thread at `addr` as the idle thread of a CPU. Idle threads are reported with
+    /// debug id 0 like the swapper tasks of Linux, as there is one per CPU they aren't indexed by
+    /// it.
+    pub fn set_idle(&mut self, addr: KobjAddr) {
+        let Some(obj) = self.objects.get_mut(&addr) else {
+            return;
+        };
+        let old_id = obj.id();
+        obj.set_id(DbgId(0));
+        if let Some(old_id) = old_id {
+            self.unindex(addr, old_id);
+        }
+    }
+
+    /// The thread receiving IPC sent to the object with the given debug id, which is either a
+    /// thread or a gate bound to a thread
+    pub fn ipc_receiver(&self, dbg_id: DbgId) -> Option<KobjAddr> {
+        match self.get_by_dbg_id(dbg_id)? {
+            (addr, KernelObject::Thread(_)) => Some(addr),
+            (_, KernelObject::Gate(g)) => Some(g.thread),
+            _ => None,
+        }
+    }
+
+    /// Binds the gate with debug id `gate` to the thread with debug id `thread`, as seen in
+    /// `GATE` events
+    pub fn bind_gate(&mut self, gate: DbgId, thread: DbgId) {
+        let Some((thread, _)) = self.get_by_dbg_id(thread) else {
+            return;
+        };
+        let Some((addr, _)) = self.get_by_dbg_id(gate) else {
+            return;
+        };
+
+        let Some(o) = self.get_mut(&addr) else {
+            return;
+        };
+        match o {
+            KernelObject::Gate(g) => g.thread = thread,
+            KernelObject::Generic(base) => {
+                let mut base = base.clone();
+                base.kobj_type = Some(KobjType::Gate);
+                *o = KernelObject::Gate(GateObject { base, thread });
+            }
+            _ => (),
+        }
+    }
+
+    /// Human readable identification of the object at `addr`: its name, its debug id if it has no
+    /// name, or the address if neither is known
+    pub fn label(&self, addr: KobjAddr) -> String {
+        match self.get(&addr) {
+            Some(o) if !o.name().is_empty() => o.name().to_string(),
+            Some(o) => o.id().map_or_else(|| addr.to_string(), |id| id.to_string()),
+            None => addr.to_string(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&KobjAddr, &KernelObject)> {
+        self.objects.iter()
+    }
+
+    /// The scheduling context `id` of the thread at `owner`, created on first use
+    pub fn sched_context_mut(&mut self, owner: KobjAddr, id: u16) -> &mut SchedContext {
+        self.sched_contexts.entry((owner, id)).or_default()
+    }
+
+    /// All scheduling contexts with the address of their owning thread and their id
+    pub fn sched_contexts(&self) -> impl Iterator<Item = (&(KobjAddr, u16), &SchedContext)> {
+        self.sched_contexts.iter()
+    }
+
+    fn unindex(&mut self, addr: KobjAddr, dbg_id: DbgId) {
+        if self.by_dbg_id.get(&dbg_id) == Some(&addr) {
+            self.by_dbg_id.remove(&dbg_id);
+        }
+    }
+
+    /// The pid to report for a thread running in `task`, which is the debug id of the task.
+    /// Threads without a known task are reported as the task of their preset, if any. `None` if
+    /// the task isn't known, callers report the thread as its own process then.
+    pub fn task_pid(&self, task: Option<KobjAddr>, tid: i64) -> Option<i64> {
+        task.and_then(|t| self.get(&t))
+            .and_then(|o| o.id())
+            .map(|id| id.0 as i64)
+            .or_else(|| {
+                self.presets
+                    .get(&DbgId(tid as u64))
+                    .and_then(|p| p.task)
+                    .map(|t| t.0 as i64)
+            })
+    }
+
+    /// Human readable identification of the task with debug id `pid` (see [`Self::task_pid`])
+    pub fn task_label(&self, pid: i64) -> String {
+        match self.get_by_dbg_id(DbgId(pid as u64)) {
+            Some((addr, _)) => self.label(addr),
+            None => pid.to_string(),
+        }
+    }
+
+    /// Marks the object at `addr` as a task, in case it wasn't typed so far
+    pub fn mark_task(&mut self, addr: KobjAddr) {
+        if let Some(o) = self.get_mut(&addr)
+            && let KernelObject::Generic(base) = o
+        {
+            let mut base = base.clone();
+            base.kobj_type = Some(KobjType::Task);
+            *o = KernelObject::Task(TaskObject { base });
+        }
+    }
+
+    /// Associates the thread at `thread` with the task at `task`
+    pub fn link_thread_to_task(&mut self, thread: KobjAddr, task: KobjAddr) {
+        self.mark_task(task);
+        if let Some(KernelObject::Thread(t)) = self.get_mut(&thread) {
+            t.task = Some(task);
+        }
+    }
 }
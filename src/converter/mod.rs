@@ -1,38 +1,72 @@
 mod convert;
-mod event;
+pub(crate) mod event;
 pub mod interruptor;
 pub mod kernel_object;
-mod plugin;
+pub(crate) mod plugin;
 mod types;
 
-use crate::event::Event;
 use crate::opts::Opts;
-use babeltrace2_sys::{CtfPluginSinkFsInitParams, EncoderPipeline, RunStatus, SourcePluginHandler};
+use crate::sink::error::Error;
+use crate::sink::{SinkEvent, TraceSink};
+use babeltrace2_sys::{
+    CtfPluginSinkFsInitParams, EncoderPipeline, LoggingLevel, RunStatus, SourcePluginHandler,
+};
 use interruptor::Interruptor;
-use kernel_object::KernelObject;
-use plugin::{TrcPlugin, TrcPluginState};
+use kernel_object::KernelObjectMap;
+use log::{debug, error};
+use plugin::TrcPluginState;
 use std::cell::{Cell, RefCell};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::ffi::CString;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+pub use plugin::{TraceParams, TrcPlugin};
+
 const CTX_MASK: u64 = 0xFFFFFFFFFFFFF000;
 
+/// Settings of the converters of all CPUs
+#[derive(Debug, Clone)]
+pub struct ConverterParams {
+    /// The trace of each CPU is written to `<output>_<cpu>`, see [`cpu_output`]
+    pub output: PathBuf,
+    /// babeltrace2 log level
+    pub log_level: LoggingLevel,
+    pub trace: TraceParams,
+}
+
+impl From<&Opts> for ConverterParams {
+    fn from(opts: &Opts) -> Self {
+        Self {
+            output: opts.output.clone(),
+            log_level: opts.log_level,
+            trace: opts.into(),
+        }
+    }
+}
+
+/// Directory of the trace of a single CPU, before the traces are merged into `output`
+pub fn cpu_output(output: &Path, cpu_id: u8) -> PathBuf {
+    PathBuf::from(format!("{}_{cpu_id}", output.display()))
+}
+
 pub struct Converter {
     pipeline: EncoderPipeline,
 }
 
 impl Converter {
     pub fn new(
-        events: Rc<RefCell<VecDeque<Event>>>,
+        events: Rc<RefCell<VecDeque<SinkEvent>>>,
         eof_signal: Rc<Cell<bool>>,
-        opts: Opts,
+        discarded: Rc<Cell<u64>>,
+        params: &ConverterParams,
         cpu_id: u8,
         intr: Interruptor,
-        kernel_object_map: Rc<RefCell<HashMap<u64, KernelObject>>>,
+        kernel_object_map: Rc<RefCell<KernelObjectMap>>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let output_path = CString::new(opts.output.to_str().unwrap())?;
-        let params = CtfPluginSinkFsInitParams::new(
+        let output = cpu_output(&params.output, cpu_id);
+        let output_path = CString::new(output.to_str().ok_or("Output path is not UTF-8")?)?;
+        let sink_params = CtfPluginSinkFsInitParams::new(
             Some(true), // assume_single_trace
             None,       // ignore_discarded_events
             None,       // ignore_discarded_packets
@@ -43,14 +77,15 @@ impl Converter {
         let state_inner: Box<dyn SourcePluginHandler> = Box::new(TrcPluginState::new(
             intr,
             events,
-            &opts,
+            &params.trace,
             eof_signal,
+            discarded,
             cpu_id,
             kernel_object_map,
         )?);
         let state = Box::new(state_inner);
 
-        let pipeline = EncoderPipeline::new::<TrcPlugin>(opts.log_level, state, &params)?;
+        let pipeline = EncoderPipeline::new::<TrcPlugin>(params.log_level, state, &sink_params)?;
 
         Ok(Self { pipeline })
     }
@@ -70,3 +105,133 @@ impl Converter {
         Ok(run_status)
     }
 }
+
+// the converter of a CPU and what its source pulls the events from
+struct CpuStream {
+    converter: Converter,
+    events: Rc<RefCell<VecDeque<SinkEvent>>>,
+    // events discarded before the conversion, not yet reported in the stream
+    discarded: Rc<Cell<u64>>,
+}
+
+/// The CTF writer as a [`TraceSink`]. The events of every CPU are queued for the babeltrace
+/// source of the CPU, whose graph writes them to [`cpu_output`].
+pub struct CtfSink {
+    params: ConverterParams,
+    interruptor: Interruptor,
+    kernel_object_map: Rc<RefCell<KernelObjectMap>>,
+    eof_signal: Rc<Cell<bool>>,
+    streams: BTreeMap<u8, CpuStream>,
+    // discarded events of CPUs without a stream yet
+    pending_discarded: HashMap<u8, u64>,
+    error: Option<(u8, String)>,
+    interrupted: bool,
+}
+
+impl CtfSink {
+    /// `kernel_object_map` are the objects tracked by the
+    /// [`Enricher`](crate::enrich::Enricher), for the statedump at the beginning of every stream
+    pub fn new(
+        params: ConverterParams,
+        interruptor: Interruptor,
+        kernel_object_map: Rc<RefCell<KernelObjectMap>>,
+    ) -> Self {
+        Self {
+            params,
+            interruptor,
+            kernel_object_map,
+            eof_signal: Rc::new(Cell::new(false)),
+            streams: BTreeMap::new(),
+            pending_discarded: HashMap::new(),
+            error: None,
+            interrupted: false,
+        }
+    }
+
+    /// The CPUs of which events were converted
+    pub fn cpus(&self) -> Vec<u8> {
+        self.streams.keys().copied().collect()
+    }
+
+    /// The CPU of which the converter couldn't be created and why
+    pub fn take_error(&mut self) -> Option<(u8, String)> {
+        self.error.take()
+    }
+
+    /// True once the graph ended early because the interruptor was set
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    // the stream of the CPU, created on its first event
+    fn stream(&mut self, cpu_id: u8) -> Option<&mut CpuStream> {
+        if !self.streams.contains_key(&cpu_id) {
+            debug!("Instantiating converter {cpu_id}");
+            let events: Rc<RefCell<VecDeque<SinkEvent>>> = Default::default();
+            let discarded = Rc::new(Cell::new(
+                self.pending_discarded.remove(&cpu_id).unwrap_or(0),
+            ));
+            let converter = Converter::new(
+                events.clone(),
+                self.eof_signal.clone(),
+                discarded.clone(),
+                &self.params,
+                cpu_id,
+                self.interruptor.clone(),
+                self.kernel_object_map.clone(),
+            );
+            match converter {
+                Ok(converter) => {
+                    self.streams.insert(
+                        cpu_id,
+                        CpuStream {
+                            converter,
+                            events,
+                            discarded,
+                        },
+                    );
+                }
+                Err(e) => {
+                    self.error.get_or_insert((cpu_id, e.to_string()));
+                    return None;
+                }
+            }
+        }
+        self.streams.get_mut(&cpu_id)
+    }
+}
+
+impl TraceSink for CtfSink {
+    fn event(&mut self, event: &SinkEvent) {
+        let Some(stream) = self.stream(event.cpu) else {
+            return;
+        };
+
+        debug!("Received event \n {:?}", event.event);
+        stream.events.borrow_mut().push_back(event.clone());
+        debug!("Trying to convert event...");
+        match stream.converter.convert_once() {
+            Ok(RunStatus::End) => self.interrupted = true,
+            Ok(_) => debug!("Succesfully converted event"),
+            Err(e) => error!("Error converting event ({:?})", e),
+        }
+    }
+
+    fn discarded(&mut self, cpu: u8, count: u64) {
+        match self.streams.get(&cpu) {
+            Some(stream) => stream.discarded.set(stream.discarded.get() + count),
+            None => *self.pending_discarded.entry(cpu).or_default() += count,
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.eof_signal.set(true);
+        for stream in self.streams.values_mut() {
+            match stream.converter.convert() {
+                Ok(_) => debug!("Succesfully closed converter stream"),
+                Err(e) => error!("Error closing converter stream ({:?})", e),
+            }
+        }
+        Ok(())
+    }
+}
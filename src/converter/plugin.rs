@@ -1,16 +1,16 @@
+use super::event::statedump::StatedumpEntry;
 use super::interruptor::Interruptor;
-use super::kernel_object::KernelObject;
+use super::kernel_object::KernelObjectMap;
 use super::{convert::TrcCtfConverter, types::BorrowedCtfState};
-use crate::event::Event;
 use crate::opts::Opts;
+use crate::sink::SinkEvent;
 use babeltrace2_sys::{
     BtResult, BtResultExt, Error, MessageIteratorStatus, Plugin, SelfComponent,
     SelfMessageIterator, SourcePluginDescriptor, SourcePluginHandler, ffi,
-    source_plugin_descriptors,
 };
 use chrono::prelude::{DateTime, Utc};
 use std::cell::{Cell, RefCell};
-use std::collections::{HashMap, VecDeque};
+use std::collections::VecDeque;
 use std::rc::Rc;
 use std::{
     ffi::{CStr, CString},
@@ -18,15 +18,40 @@ use std::{
 };
 use tracing::debug;
 
+/// Names and clock of the trace created by a [`TrcPluginState`]
+#[derive(Debug, Clone)]
+pub struct TraceParams {
+    pub clock_name: String,
+    pub clock_frequency: u64,
+    pub trace_name: String,
+}
+
+impl From<&Opts> for TraceParams {
+    fn from(opts: &Opts) -> Self {
+        Self {
+            clock_name: opts.clock_name.clone(),
+            clock_frequency: opts.clock_frequency,
+            trace_name: opts.trace_name.clone(),
+        }
+    }
+}
+
+// messages of an event and the discarded events message before it
+const MAX_MESSAGES_PER_EVENT: usize = TrcCtfConverter::MAX_MESSAGES_PER_EVENT + 1;
+
 pub struct TrcPluginState {
     interruptor: Interruptor,
-    events: Rc<RefCell<VecDeque<Event>>>,
+    events: Rc<RefCell<VecDeque<SinkEvent>>>,
+    // the tracked kernel objects, for the statedump at the beginning of the stream
+    kernel_object_map: Rc<RefCell<KernelObjectMap>>,
     clock_name: CString,
     clock_frequency: u64,
     trace_name: CString,
     trace_creation_time: DateTime<Utc>,
     first_event_observed: bool,
     eof_reached: Rc<Cell<bool>>,
+    // events of this CPU discarded before the conversion, not yet reported in the stream
+    discarded: Rc<Cell<u64>>,
     stream_is_open: bool,
     stream: *mut ffi::bt_stream,
     packet: *mut ffi::bt_packet,
@@ -37,46 +62,49 @@ pub struct TrcPluginState {
 impl TrcPluginState {
     pub fn new(
         interruptor: Interruptor,
-        events: Rc<RefCell<VecDeque<Event>>>,
-        opts: &Opts,
+        events: Rc<RefCell<VecDeque<SinkEvent>>>,
+        params: &TraceParams,
         eof_signal: Rc<Cell<bool>>,
+        discarded: Rc<Cell<u64>>,
         cpu_id: u8,
-        kernel_object_map: Rc<RefCell<HashMap<u64, KernelObject>>>,
+        kernel_object_map: Rc<RefCell<KernelObjectMap>>,
     ) -> Result<Self, Error> {
-        let clock_name = CString::new(opts.clock_name.as_str())?;
-        let clock_frequency = opts.clock_frequency;
-        let trace_name = CString::new(opts.trace_name.as_str())?;
+        let clock_name = CString::new(params.clock_name.as_str())?;
+        let clock_frequency = params.clock_frequency;
+        let trace_name = CString::new(params.trace_name.as_str())?;
         Ok(Self {
             interruptor,
             events,
+            kernel_object_map,
             clock_name,
             clock_frequency,
             trace_name,
             trace_creation_time: Utc::now(),
             first_event_observed: false,
             eof_reached: eof_signal,
+            discarded,
             stream_is_open: false,
             // NOTE: timestamp/event trackers get re-initialized on the first event
             stream: ptr::null_mut(),
             packet: ptr::null_mut(),
             cpu_id,
-            converter: TrcCtfConverter::new(kernel_object_map),
+            converter: TrcCtfConverter::new(),
         })
     }
 
     pub fn create_metadata_and_stream_objects(
         &mut self,
-        mut component: SelfComponent,
+        component: *mut ffi::bt_self_component,
     ) -> Result<(), Error> {
         unsafe {
-            let trace_class = ffi::bt_trace_class_create(component.inner_mut());
+            let trace_class = ffi::bt_trace_class_create(component);
             ffi::bt_trace_class_set_assigns_automatic_stream_class_id(trace_class, 0);
 
             // Create common event context
             let base_event_context = self.converter.create_event_common_context(trace_class)?;
 
             // Setup the default clock class
-            let clock_class = ffi::bt_clock_class_create(component.inner_mut());
+            let clock_class = ffi::bt_clock_class_create(component);
             let ret =
                 ffi::bt_clock_class_set_name(clock_class, self.clock_name.as_c_str().as_ptr());
             ret.capi_result()?;
@@ -199,7 +227,7 @@ impl TrcPluginState {
         Ok(())
     }
 
-    pub fn read_event(&mut self) -> Result<Option<Event>, Error> {
+    pub fn read_event(&mut self) -> Result<Option<SinkEvent>, Error> {
         if let Some(event) = self.events.borrow_mut().pop_front() {
             Ok(Some(event))
         } else {
@@ -209,21 +237,37 @@ impl TrcPluginState {
 
     pub fn process_event(
         &mut self,
-        event: Event,
+        event: SinkEvent,
         ctf_state: &mut BorrowedCtfState,
     ) -> Result<(), Error> {
         if !self.first_event_observed {
             self.first_event_observed = true;
         }
 
-        self.converter.convert(event, ctf_state)?;
+        self.push_discarded(ctf_state)?;
+        self.converter.convert(&event, ctf_state)?;
 
         Ok(())
     }
-}
 
-impl SourcePluginHandler for TrcPluginState {
-    fn initialize(&mut self, component: SelfComponent) -> Result<(), Error> {
+    /// Reports the events discarded since the last call in a discarded events message
+    pub fn push_discarded(&mut self, ctf_state: &mut BorrowedCtfState) -> Result<(), Error> {
+        let count = self.discarded.take();
+        if count == 0 {
+            return Ok(());
+        }
+
+        let msg = unsafe {
+            ffi::bt_message_discarded_events_create(ctf_state.message_iter_mut(), self.stream)
+        };
+        if !msg.is_null() {
+            unsafe { ffi::bt_message_discarded_events_set_count(msg, count) };
+        }
+        ctf_state.push_message(msg)
+    }
+
+    /// Creates the trace, clock, stream and event classes in `component`
+    pub fn init(&mut self, component: *mut ffi::bt_self_component) -> Result<(), Error> {
         self.create_metadata_and_stream_objects(component)?;
         self.set_trace_env()?;
 
@@ -233,7 +277,8 @@ impl SourcePluginHandler for TrcPluginState {
         Ok(())
     }
 
-    fn finalize(&mut self, _component: SelfComponent) -> Result<(), Error> {
+    /// Puts the references to the stream and its packet
+    pub fn release(&mut self) -> Result<(), Error> {
         unsafe {
             assert!(!self.packet.is_null());
             ffi::bt_packet_put_ref(self.packet);
@@ -247,9 +292,10 @@ impl SourcePluginHandler for TrcPluginState {
         Ok(())
     }
 
-    fn iterator_next(
+    /// Fills `messages` with the messages of the next queued event
+    pub fn next(
         &mut self,
-        msg_iter: SelfMessageIterator,
+        msg_iter: *mut ffi::bt_self_message_iterator,
         messages: &mut [*const ffi::bt_message],
     ) -> Result<MessageIteratorStatus, Error> {
         assert!(!self.stream.is_null());
@@ -260,6 +306,8 @@ impl SourcePluginHandler for TrcPluginState {
             debug!("Early shutdown");
             self.eof_reached.set(true);
 
+            self.push_discarded(&mut ctf_state)?;
+
             // Add packet end message
             let msg = unsafe {
                 ffi::bt_message_packet_end_create(ctf_state.message_iter_mut(), self.packet)
@@ -299,6 +347,23 @@ impl SourcePluginHandler for TrcPluginState {
                         )
                     };
                     ctf_state.push_message(msg)?;
+
+                    let entries = StatedumpEntry::snapshot(&self.kernel_object_map.borrow());
+                    self.converter
+                        .begin_statedump(entries, event.tsc, self.cpu_id);
+                }
+
+                if self.converter.statedump_pending() {
+                    self.converter.emit_statedump(&mut ctf_state)?;
+                }
+
+                // the statedump may need more than one batch, so hold the event back until there
+                // is room for all messages it may produce
+                if self.converter.statedump_pending()
+                    || ctf_state.remaining() < MAX_MESSAGES_PER_EVENT
+                {
+                    self.events.borrow_mut().push_front(event);
+                    return Ok(ctf_state.release());
                 }
 
                 // TODO need to put_ref(msg) on this and/or all of the msgs?
@@ -314,6 +379,8 @@ impl SourcePluginHandler for TrcPluginState {
                     debug!("End of file reached");
                     self.eof_reached.set(true);
 
+                    self.push_discarded(&mut ctf_state)?;
+
                     // Add packet end message
                     let msg = unsafe {
                         ffi::bt_message_packet_end_create(ctf_state.message_iter_mut(), self.packet)
@@ -337,6 +404,24 @@ impl SourcePluginHandler for TrcPluginState {
     }
 }
 
+impl SourcePluginHandler for TrcPluginState {
+    fn initialize(&mut self, mut component: SelfComponent) -> Result<(), Error> {
+        self.init(component.inner_mut())
+    }
+
+    fn finalize(&mut self, _component: SelfComponent) -> Result<(), Error> {
+        self.release()
+    }
+
+    fn iterator_next(
+        &mut self,
+        mut msg_iter: SelfMessageIterator,
+        messages: &mut [*const ffi::bt_message],
+    ) -> Result<MessageIteratorStatus, Error> {
+        self.next(msg_iter.inner_mut(), messages)
+    }
+}
+
 pub struct TrcPlugin;
 
 impl SourcePluginDescriptor for TrcPlugin {
@@ -362,31 +447,3 @@ impl SourcePluginDescriptor for TrcPlugin {
         unsafe { CStr::from_bytes_with_nul_unchecked(Self::GRAPH_NODE_NAME) }
     }
 }
-
-source_plugin_descriptors!(TrcPlugin);
-
-pub mod utils_plugin_descriptors {
-    use babeltrace2_sys::ffi::*;
-
-    #[link(
-        name = "babeltrace-plugin-utils",
-        kind = "static",
-        modifiers = "+whole-archive"
-    )]
-    unsafe extern "C" {
-        pub static __bt_plugin_descriptor_auto_ptr: *const __bt_plugin_descriptor;
-    }
-}
-
-pub mod ctf_plugin_descriptors {
-    use babeltrace2_sys::ffi::*;
-
-    #[link(
-        name = "babeltrace-plugin-ctf",
-        kind = "static",
-        modifiers = "+whole-archive"
-    )]
-    unsafe extern "C" {
-        pub static __bt_plugin_descriptor_auto_ptr: *const __bt_plugin_descriptor;
-    }
-}
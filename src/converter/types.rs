@@ -1,4 +1,4 @@
-use babeltrace2_sys::{Error, MessageIteratorStatus, SelfMessageIterator, ffi};
+use babeltrace2_sys::{Error, MessageIteratorStatus, ffi};
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 
@@ -40,7 +40,7 @@ impl StringCache {
 pub struct BorrowedCtfState<'a> {
     stream: *mut ffi::bt_stream,
     packet: *mut ffi::bt_packet,
-    msg_iter: SelfMessageIterator,
+    msg_iter: *mut ffi::bt_self_message_iterator,
     messages: &'a mut [*const ffi::bt_message],
     msgs_len: usize,
 }
@@ -49,11 +49,12 @@ impl<'a> BorrowedCtfState<'a> {
     pub fn new(
         stream: *mut ffi::bt_stream,
         packet: *mut ffi::bt_packet,
-        msg_iter: SelfMessageIterator,
+        msg_iter: *mut ffi::bt_self_message_iterator,
         messages: &'a mut [*const ffi::bt_message],
     ) -> Self {
         assert!(!stream.is_null());
         assert!(!packet.is_null());
+        assert!(!msg_iter.is_null());
         assert!(!messages.is_empty());
         Self {
             stream,
@@ -77,7 +78,7 @@ impl<'a> BorrowedCtfState<'a> {
     }
 
     pub fn message_iter_mut(&mut self) -> *mut ffi::bt_self_message_iterator {
-        self.msg_iter.inner_mut()
+        self.msg_iter
     }
 
     pub fn create_message(
@@ -87,7 +88,7 @@ impl<'a> BorrowedCtfState<'a> {
     ) -> *mut ffi::bt_message {
         unsafe {
             ffi::bt_message_event_create_with_packet_and_default_clock_snapshot(
-                self.msg_iter.inner_mut(),
+                self.msg_iter,
                 event_class,
                 self.packet,
                 timestamp,
@@ -95,6 +96,16 @@ impl<'a> BorrowedCtfState<'a> {
         }
     }
 
+    /// True if no more messages can be pushed in this iteration
+    pub fn is_full(&self) -> bool {
+        self.msgs_len >= self.messages.len()
+    }
+
+    /// Number of messages which can still be pushed in this iteration
+    pub fn remaining(&self) -> usize {
+        self.messages.len().saturating_sub(self.msgs_len)
+    }
+
     pub fn push_message(&mut self, msg: *const ffi::bt_message) -> Result<(), Error> {
         if msg.is_null() {
             Err(Error::PluginError("MessageVec: msg is NULL".to_owned()))
@@ -0,0 +1,10 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("IO error on creating the database")]
+    Io(#[from] io::Error),
+    #[error("SQLite error")]
+    Sqlite(#[from] rusqlite::Error),
+}
@@ -0,0 +1,11 @@
+-- Indexes of the trace database, created after all data is inserted
+
+CREATE INDEX events_tsc ON events (tsc);
+CREATE INDEX events_cpu ON events (cpu, tsc);
+CREATE INDEX events_tid ON events (tid, tsc);
+CREATE INDEX events_type ON events (type);
+CREATE INDEX kernel_objects_id ON kernel_objects (id);
+CREATE INDEX thread_states_tid ON thread_states (tid, start_tsc);
+CREATE INDEX thread_states_start ON thread_states (start_tsc);
+CREATE INDEX ipc_pairs_start ON ipc_pairs (start_tsc);
+CREATE INDEX ipc_pairs_gate ON ipc_pairs (gate);
@@ -0,0 +1,260 @@
+//! SQLite export (`--sqlite <file>`) of the decoded events, the kernel objects, the thread state
+//! intervals and the IPC pairs, for ad-hoc queries like
+//!
+//! ```sql
+//! SELECT gate, count(*), max(latency_ns) FROM ipc_pairs WHERE start_ns < 2000000000
+//!     GROUP BY gate ORDER BY max(latency_ns) DESC;
+//! ```
+//!
+//! The schema is documented in `schema.sql`. Everything is written in a single transaction, the
+//! indexes of `indexes.sql` are created at the end.
+
+pub mod error;
+
+use crate::analysis::ticks_to_ns;
+use crate::converter::kernel_object::{KernelObjectMap, ThreadState, UNKNOWN_TID};
+use crate::event::Event;
+use crate::names::ObjectEntry;
+use crate::names::export::object_table;
+use crate::sink::{Detail, SinkEvent, ThreadInfo, TraceSink};
+use error::Error;
+use rusqlite::{Connection, params};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const SCHEMA: &str = include_str!("schema.sql");
+const INDEXES: &str = include_str!("indexes.sql");
+
+// debug id of the idle threads, one per CPU
+const IDLE_TID: i64 = 0;
+
+// the open state interval of a thread, the idle threads share their tid and are told apart by
+// their CPU
+type StateKey = (i64, Option<u8>);
+
+// the state a thread is in since `start_tsc`
+struct OpenState {
+    state: ThreadState,
+    cpu: u8,
+    start_tsc: u64,
+}
+
+pub struct Database {
+    conn: Connection,
+    clock_frequency: u64,
+    states: HashMap<StateKey, OpenState>,
+    last_tsc: u64,
+    // the first error, later events are skipped
+    error: Option<Error>,
+}
+
+impl Database {
+    /// Creates the database at `path`, replacing an existing file
+    pub fn create(path: &Path, clock_frequency: u64) -> Result<Self, Error> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let conn = Connection::open(path)?;
+        // the database is written once, so don't pay for crash safety
+        conn.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+        conn.execute_batch(SCHEMA)?;
+        conn.execute_batch("BEGIN")?;
+
+        Ok(Self {
+            conn,
+            clock_frequency,
+            states: HashMap::new(),
+            last_tsc: 0,
+            error: None,
+        })
+    }
+
+    fn ns(&self, tsc: u64) -> i64 {
+        ticks_to_ns(tsc, self.clock_frequency) as i64
+    }
+
+    fn insert_event(&mut self, event: &SinkEvent) -> Result<(), Error> {
+        let common = event.event.event_common();
+        self.conn
+            .prepare_cached(
+                "INSERT INTO events (number, type, cpu, tsc, ns, ip, ctx, tid, pid, payload)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?
+            .execute(params![
+                common.number as i64,
+                event.event.to_string(),
+                event.cpu,
+                event.tsc as i64,
+                self.ns(event.tsc),
+                common.ip as i64,
+                common.ctx as i64,
+                event.thread.tid,
+                event.thread.pid,
+                event.payload().to_string(),
+            ])?;
+        Ok(())
+    }
+
+    // ends the interval of the thread if its state changed, the unknown threads can't be told
+    // apart and have no intervals
+    fn thread_state(&mut self, thread: &ThreadInfo, cpu: u8, tsc: u64) -> Result<(), Error> {
+        let Some(state) = thread.state else {
+            return Ok(());
+        };
+        if thread.tid == UNKNOWN_TID {
+            return Ok(());
+        }
+        let key = (thread.tid, (thread.tid == IDLE_TID).then_some(cpu));
+        if self.states.get(&key).is_some_and(|s| s.state == state) {
+            return Ok(());
+        }
+
+        let open = OpenState {
+            state,
+            cpu,
+            start_tsc: tsc,
+        };
+        if let Some(prev) = self.states.insert(key, open) {
+            self.insert_state(thread.tid, &prev, tsc)?;
+        }
+        Ok(())
+    }
+
+    fn insert_state(&self, tid: i64, state: &OpenState, end_tsc: u64) -> Result<(), Error> {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO thread_states (tid, state, cpu, start_tsc, end_tsc, start_ns, end_ns)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?
+            .execute(params![
+                tid,
+                state.state.as_str(),
+                state.cpu,
+                state.start_tsc as i64,
+                end_tsc as i64,
+                self.ns(state.start_tsc),
+                self.ns(end_tsc),
+            ])?;
+        Ok(())
+    }
+
+    // the state intervals of the threads of a context switch, also of left out ones
+    fn context_switch(&mut self, event: &SinkEvent) -> Result<(), Error> {
+        self.last_tsc = self.last_tsc.max(event.tsc);
+        for detail in &event.details {
+            if let Detail::ContextSwitch { prev, next, .. } = detail {
+                self.thread_state(prev, event.cpu, event.tsc)?;
+                self.thread_state(next, event.cpu, event.tsc)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, event: &SinkEvent) -> Result<(), Error> {
+        self.insert_event(event)?;
+        self.context_switch(event)?;
+
+        for detail in &event.details {
+            if let Detail::IpcCompleted(ipc) = detail
+                && let Event::IpcRes(res) = event.event
+            {
+                let start_tsc = event.tsc.saturating_sub(ipc.latency);
+                self.conn
+                    .prepare_cached(
+                        "INSERT INTO ipc_pairs (ipc_number, res_number, cpu, caller,
+                             caller_task, gate, callee, start_tsc, end_tsc, start_ns,
+                             latency_ns)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    )?
+                    .execute(params![
+                        res.pair_event as i64,
+                        res.common.number as i64,
+                        event.cpu,
+                        ipc.caller,
+                        ipc.caller_task,
+                        ipc.gate,
+                        ipc.callee,
+                        start_tsc as i64,
+                        event.tsc as i64,
+                        self.ns(start_tsc),
+                        ipc.latency_ns as i64,
+                    ])?;
+            }
+        }
+
+        self.thread_state(&event.thread, event.cpu, event.tsc)
+    }
+
+    /// Writes the final kernel object table
+    pub fn write_objects(&mut self, entries: &[ObjectEntry]) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare(
+            "INSERT INTO kernel_objects (id, addr, type, name, task, thread, prio, state, created,
+                 destroyed, generation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        for e in entries {
+            stmt.execute(params![
+                e.id.map(|v| v as i64),
+                e.addr.map(|v| v as i64),
+                e.kobj_type,
+                e.name,
+                e.task.map(|v| v as i64),
+                e.thread.map(|v| v as i64),
+                e.prio.map(|v| v as i64),
+                e.state,
+                e.created.map(|v| v as i64),
+                e.destroyed.map(|v| v as i64),
+                e.generation,
+            ])?;
+        }
+        Ok(())
+    }
+
+    // closes the open thread state intervals, creates the indexes and commits
+    fn close(&mut self) -> Result<(), Error> {
+        let states: Vec<(StateKey, OpenState)> = self.states.drain().collect();
+        for ((tid, _), state) in states {
+            self.insert_state(tid, &state, self.last_tsc)?;
+        }
+        self.conn.execute_batch(INDEXES)?;
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
+
+impl TraceSink for Database {
+    fn event(&mut self, event: &SinkEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.write(event) {
+            self.error = Some(e);
+        }
+    }
+
+    fn left_out(&mut self, event: &SinkEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.context_switch(event) {
+            self.error = Some(e);
+        }
+    }
+
+    fn kernel_objects(&mut self, objects: &KernelObjectMap) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.write_objects(&object_table(objects)) {
+            self.error = Some(e);
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), crate::sink::error::Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e.into());
+        }
+        Ok(self.close()?)
+    }
+}
@@ -0,0 +1,65 @@
+-- Schema of the SQLite trace database (`--sqlite <file>`).
+--
+-- Timestamps are given in clock ticks (`*_tsc`) and in nanoseconds (`*_ns`, using the clock
+-- frequency of the trace). Threads are identified by their debug id (`tid`), the same id as in
+-- the CTF trace, and are named in `kernel_objects`. Threads whose debug id isn't known have tid
+-- and pid -1, the idle threads of all CPUs tid 0. Addresses are 64 bit values stored as
+-- INTEGER, so kernel addresses are negative, `printf('%x', addr)` shows them in hex.
+
+-- Every decoded event
+CREATE TABLE events (
+    number INTEGER NOT NULL,     -- event number of the kernel trace buffer
+    type TEXT NOT NULL,          -- event type, e.g. 'IPC' or 'CONTEXTSWITCH'
+    cpu INTEGER NOT NULL,
+    tsc INTEGER NOT NULL,
+    ns INTEGER NOT NULL,
+    ip INTEGER NOT NULL,         -- instruction pointer in the kernel
+    ctx INTEGER NOT NULL,        -- address of the current thread
+    tid INTEGER NOT NULL,        -- the current thread
+    pid INTEGER NOT NULL,        -- debug id of its task
+    payload TEXT NOT NULL        -- the fields of the event type as JSON object, see src/event/
+);
+
+-- The final kernel object table
+CREATE TABLE kernel_objects (
+    id INTEGER,                  -- debug id
+    addr INTEGER,
+    type TEXT,                   -- 'thread', 'task', 'gate', ...
+    name TEXT NOT NULL,
+    task INTEGER,                -- threads: debug id of their task
+    thread INTEGER,              -- IPC gates: debug id of the thread they are bound to
+    prio INTEGER,                -- threads: last known priority
+    state TEXT,                  -- threads: last known state
+    created INTEGER,             -- timestamp (tsc) of the creation
+    destroyed INTEGER,           -- timestamp (tsc) of the destruction
+    generation INTEGER NOT NULL  -- number of objects which were at the same address before
+);
+
+-- The states of the threads over time, as tracked by the converter. An interval ends where the
+-- next one of the thread starts, the last ones end with the last event of the trace. The idle
+-- threads (tid 0) have separate intervals per CPU, the threads with tid -1 have none, as they
+-- can't be told apart.
+CREATE TABLE thread_states (
+    tid INTEGER NOT NULL,
+    state TEXT NOT NULL,         -- 'running', 'runnable', 'ipc_recv_wait', ...
+    cpu INTEGER NOT NULL,        -- CPU of the event which started the interval
+    start_tsc INTEGER NOT NULL,
+    end_tsc INTEGER NOT NULL,
+    start_ns INTEGER NOT NULL,
+    end_ns INTEGER NOT NULL
+);
+
+-- IPCs matched with their results (IPC and IPCRES events)
+CREATE TABLE ipc_pairs (
+    ipc_number INTEGER NOT NULL, -- number of the IPC event
+    res_number INTEGER NOT NULL, -- number of the IPCRES event
+    cpu INTEGER NOT NULL,        -- CPU of the IPCRES event
+    caller TEXT NOT NULL,        -- name of the calling thread
+    caller_task TEXT,
+    gate TEXT NOT NULL,          -- name of the gate or thread the IPC was sent to
+    callee TEXT NOT NULL,        -- name of the thread receiving through the gate
+    start_tsc INTEGER NOT NULL,
+    end_tsc INTEGER NOT NULL,
+    start_ns INTEGER NOT NULL,
+    latency_ns INTEGER NOT NULL
+);
@@ -0,0 +1,436 @@
+//! Enrichment of the parsed events, independent of the output formats.
+//!
+//! The [`Enricher`] tracks the kernel objects ([`KernelObjectMap`]) through the events, asks the
+//! [`EventFilter`] and the [`Sampler`] whether to keep an event, pairs the IPCs with their results
+//! and flows and turns every kept event into a [`SinkEvent`] with the names, symbols and
+//! [`Detail`]s derived from it. The CTF writer and the other [`TraceSink`](crate::sink::TraceSink)s only format what
+//! they are handed.
+
+mod sched;
+
+use crate::analysis::ipc_flow::IpcFlows;
+use crate::analysis::ipc_latency::{IpcLatency, PendingIpc};
+use crate::converter::event::ipc_decode::{LABEL_PAGE_FAULT, MsgTag};
+use crate::converter::event::ipc_type::IpcType;
+use crate::converter::kernel_object::{
+    BaseKernelObject, DbgId, GateObject, KernelObject, KernelObjectMap, KobjAddr, KobjType,
+    TaskObject, ThreadObject, ThreadState,
+};
+use crate::event::Event;
+use crate::event::nam::NamEvent;
+use crate::filter::EventFilter;
+use crate::helpers;
+use crate::sampling::Sampler;
+use crate::sink::{Detail, EventContext, SinkEvent, ThreadInfo};
+use crate::symbols::Symbolizer;
+use log::info;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+pub struct Enricher {
+    kernel_object_map: Rc<RefCell<KernelObjectMap>>,
+    symbolizer: Symbolizer,
+    filter: EventFilter,
+    sampler: Sampler,
+    ipc_latency: IpcLatency,
+    ipc_flows: IpcFlows,
+    // the thread each CPU switched to last
+    last_sched_in: HashMap<u8, Option<ThreadObject>>,
+    // threads whose task became known, not yet reported to the sinks
+    unreported_links: VecDeque<KobjAddr>,
+    // threads whose last event was a page fault, they wait for it only if it blocks them
+    faulting: HashSet<KobjAddr>,
+    // the context switch of the last processed event, if it was left out
+    left_out: Option<SinkEvent>,
+}
+
+impl Enricher {
+    pub fn new(
+        kernel_object_map: KernelObjectMap,
+        symbolizer: Symbolizer,
+        filter: EventFilter,
+        sampler: Sampler,
+        clock_frequency: u64,
+    ) -> Self {
+        Self {
+            kernel_object_map: Rc::new(RefCell::new(kernel_object_map)),
+            symbolizer,
+            filter,
+            sampler,
+            ipc_latency: IpcLatency::new(clock_frequency),
+            ipc_flows: IpcFlows::new(clock_frequency),
+            last_sched_in: HashMap::new(),
+            unreported_links: VecDeque::new(),
+            faulting: HashSet::new(),
+            left_out: None,
+        }
+    }
+
+    /// The tracked kernel objects, shared with the CTF writer for its statedumps
+    pub fn kernel_object_map(&self) -> Rc<RefCell<KernelObjectMap>> {
+        self.kernel_object_map.clone()
+    }
+
+    pub fn filter(&self) -> &EventFilter {
+        &self.filter
+    }
+
+    /// Number of events discarded by the sampler per event name
+    pub fn discarded(&self) -> &BTreeMap<String, u64> {
+        self.sampler.discarded()
+    }
+
+    /// Number of events of `cpu` discarded by the sampler since the last call, to be reported
+    /// before the next event of the CPU
+    pub fn take_discarded(&mut self, cpu: u8) -> u64 {
+        self.sampler.take_pending(cpu)
+    }
+
+    /// The CPUs and numbers of the events discarded after the last kept event of the CPU
+    pub fn drain_discarded(&mut self) -> impl Iterator<Item = (u8, u64)> + '_ {
+        self.sampler.drain_pending()
+    }
+
+    /// The last processed event if it is a context switch the filter or the sampler left out, for
+    /// the sinks which follow the threads on the CPUs (see [`TraceSink::left_out`](crate::sink::TraceSink::left_out))
+    pub fn take_left_out(&mut self) -> Option<SinkEvent> {
+        self.left_out.take()
+    }
+
+    /// The final kernel object model and IPC latencies
+    pub fn into_state(self) -> (KernelObjectMap, IpcLatency) {
+        (self.kernel_object_map.take(), self.ipc_latency)
+    }
+
+    /// Tracks the kernel objects of `event` and returns it with everything derived from it, or
+    /// `None` if the filter leaves it out or the sampler discards it
+    pub fn process(&mut self, event: Event) -> Option<SinkEvent> {
+        let common = event.event_common();
+        let ctx = KobjAddr::masked(common.ctx);
+        let task_before = self.thread_task(ctx);
+        let mut details = self.track(&event);
+
+        // `sched_switch` has no pid, so the sinks are told about the task of a thread once it is
+        // known (by a context switch or page fault in it), also if the event is filtered
+        if let Some(task) = self.thread_task(ctx)
+            && task_before != Some(task)
+        {
+            self.unreported_links.push_back(ctx);
+        }
+
+        // the kernel objects are tracked for every event, filtered and discarded ones are left out
+        // of the analyses and the sinks. Only kept events are sampled, so the discarded counts
+        // don't include filtered events.
+        let keep = self.filter.keep(&event, &self.kernel_object_map.borrow());
+        if !keep || !self.sampler.keep(&event) {
+            self.skip(&event);
+            if details
+                .iter()
+                .any(|d| matches!(d, Detail::ContextSwitch { .. }))
+            {
+                self.left_out = Some(self.sink_event(event, details));
+            }
+            return None;
+        }
+        self.analyze(&event, &mut details);
+
+        // one at a time, which bounds the messages of a single event in the CTF streams
+        if let Some(thread) = self.unreported_links.pop_front() {
+            let map = self.kernel_object_map.borrow();
+            details.push(Detail::TaskLinked(ThreadInfo::resolve(&map, thread)));
+        }
+        Some(self.sink_event(event, details))
+    }
+
+    fn sink_event(&self, event: Event, details: Vec<Detail>) -> SinkEvent {
+        let map = self.kernel_object_map.borrow();
+        let common = event.event_common();
+        SinkEvent {
+            event,
+            cpu: common.cpu,
+            tsc: common.tsc,
+            thread: ThreadInfo::resolve(&map, KobjAddr::masked(common.ctx)),
+            context: self.context(&map, &event),
+            details,
+        }
+    }
+
+    // updates the kernel objects with what the event tells about them
+    fn track(&mut self, event: &Event) -> Vec<Detail> {
+        let mut map = self.kernel_object_map.borrow_mut();
+        let tsc = event.event_common().tsc;
+        let ctx = KobjAddr::masked(event.event_common().ctx);
+        // a page fault resolved without blocking is followed by other events of the thread
+        let faulting = self.faulting.remove(&ctx);
+
+        match event {
+            Event::Nam(ev) => name_object(&mut map, ev),
+            Event::ContextSwitch(ev) => {
+                if faulting && let Some(KernelObject::Thread(t)) = map.get_mut(&ctx) {
+                    t.state = ThreadState::PageFaultWait;
+                }
+                let last_sched_in = self.last_sched_in.entry(ev.common.cpu).or_default();
+                return sched::context_switch(&mut map, last_sched_in, ev)
+                    .into_iter()
+                    .collect();
+            }
+            Event::Ipc(ev) => {
+                if let Some(KernelObject::Thread(t)) = map.get_mut(&ctx) {
+                    t.state = if faulting && MsgTag::decode(ev.tag).label == LABEL_PAGE_FAULT {
+                        ThreadState::PageFaultWait
+                    } else {
+                        IpcType::blocking_state((ev.dst & 0xf) as u8)
+                    };
+                }
+            }
+            Event::IpcRes(_) => {
+                if let Some(KernelObject::Thread(t)) = map.get_mut(&ctx) {
+                    t.state = ThreadState::Running;
+                }
+            }
+            Event::Destroy(ev) => {
+                // destroyed objects are kept (threads are reported as dead by their final
+                // sched_switch) until their address gets reused
+                let addr = KobjAddr::masked(ev.obj);
+                let kobj_type = map.destroy(addr, ev.type_, tsc);
+                let (name, generation, created) = match map.get(&addr) {
+                    Some(o) => (o.name().to_string(), o.base().generation, o.base().created),
+                    None => (String::new(), 0, None),
+                };
+                return vec![Detail::ObjectDestroyed {
+                    addr,
+                    kobj_type,
+                    name,
+                    generation,
+                    created,
+                }];
+            }
+            Event::Factory(ev) => {
+                let addr = KobjAddr::masked(ev.obj);
+                let kobj_type = KobjType::from_factory_op(ev.op);
+                let base = BaseKernelObject {
+                    kobj_type: Some(kobj_type),
+                    created: Some(tsc),
+                    ..BaseKernelObject::new(DbgId(ev.newo), "".to_string())
+                };
+                let new_obj = if kobj_type == KobjType::Task {
+                    KernelObject::Task(TaskObject { base })
+                } else {
+                    KernelObject::Generic(base)
+                };
+                map.insert(addr, new_obj);
+
+                let generation = map.get(&addr).map(|o| o.base().generation).unwrap_or(0);
+                return vec![Detail::ObjectCreated {
+                    addr,
+                    kobj_type,
+                    generation,
+                }];
+            }
+            Event::Pf(ev) => {
+                // the thread waits for its pager only once the fault leads to a page fault IPC or
+                // the thread gets switched out before its next event
+                self.faulting.insert(ctx);
+                map.link_thread_to_task(ctx, KobjAddr::masked(ev.space));
+            }
+            Event::Vcpu(ev) => {
+                // the vCPU may run in a different task than the one of its thread, so only record
+                // that there is a task at that address
+                map.mark_task(KobjAddr::masked(ev.space));
+            }
+            Event::Gate(ev) => map.bind_gate(DbgId(ev.gate_dbg_id), DbgId(ev.thread_dbg_id)),
+            Event::Sched(ev) => return sched::sched_context(&mut map, ev),
+            _ => (),
+        }
+        Vec::new()
+    }
+
+    // drops what the analyses would pair with a filtered or discarded event, so it isn't paired with a kept
+    // one instead
+    fn skip(&mut self, event: &Event) {
+        match event {
+            Event::Ipc(ev) if IpcType::is_reply((ev.dst & 0xf) as u8) => {
+                self.ipc_flows.reply_target(KobjAddr::masked(ev.common.ctx));
+            }
+            Event::IpcRes(ev) => {
+                self.ipc_latency.forget(ev.pair_event);
+                if IpcType::has_recv((ev.dst & 0xf) as u8) {
+                    self.ipc_flows.receive(
+                        KobjAddr::masked(ev.common.ctx),
+                        ev.common.tsc,
+                        ev.dword,
+                    );
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // pairs IPCs with their results and the receive phase of the receiver
+    fn analyze(&mut self, event: &Event, details: &mut Vec<Detail>) {
+        let map = self.kernel_object_map.borrow();
+        match event {
+            Event::Ipc(ev) => {
+                let tsc = ev.common.tsc;
+                let sender = KobjAddr::masked(ev.common.ctx);
+                // the same names as the thread of the event, so the graph finds the edge of the
+                // message again at its completion
+                let caller = ThreadInfo::resolve(&map, sender);
+                let (gate, callee) = match map.get_by_dbg_id(DbgId(ev.dbg_id)) {
+                    Some((_, o)) => {
+                        let callee = match o {
+                            KernelObject::Gate(g) => {
+                                map.get(&g.thread).map(|_| map.label(g.thread))
+                            }
+                            _ => None,
+                        };
+                        (o.name().to_string(), callee.unwrap_or_default())
+                    }
+                    None => (String::new(), String::new()),
+                };
+                let gate = if gate.is_empty() {
+                    ev.dbg_id.to_string()
+                } else {
+                    gate
+                };
+                let tag = MsgTag::decode(ev.tag);
+                details.push(Detail::IpcMessage {
+                    gate: gate.clone(),
+                    callee: callee.clone(),
+                    words: tag.words as u64,
+                    items: tag.items as u64,
+                });
+                self.ipc_latency.start(
+                    ev.common.number,
+                    PendingIpc {
+                        tsc,
+                        caller: caller.name,
+                        caller_task: caller.task,
+                        gate,
+                        callee,
+                    },
+                );
+
+                let type_number = (ev.dst & 0xf) as u8;
+                let receiver = if IpcType::is_reply(type_number) {
+                    self.ipc_flows.reply_target(sender)
+                } else {
+                    map.ipc_receiver(DbgId(ev.dbg_id))
+                };
+                if IpcType::has_send(type_number)
+                    && let Some(receiver) = receiver
+                {
+                    self.ipc_flows.send(
+                        ev.common.number,
+                        sender,
+                        receiver,
+                        IpcType::has_recv(type_number),
+                        tsc,
+                        ev.dword,
+                    );
+                    details.push(Detail::IpcSend {
+                        flow_id: ev.common.number,
+                        receiver: ThreadInfo::resolve(&map, receiver),
+                    });
+                }
+            }
+            Event::IpcRes(ev) => {
+                let tsc = ev.common.tsc;
+                if let Some(completed) = self.ipc_latency.finish(ev.pair_event, tsc) {
+                    details.push(Detail::IpcCompleted(completed));
+                }
+
+                let receiver = KobjAddr::masked(ev.common.ctx);
+                let received = if IpcType::has_recv((ev.dst & 0xf) as u8) {
+                    self.ipc_flows.receive(receiver, tsc, ev.dword)
+                } else {
+                    None
+                };
+                if let Some((flow_id, sender)) = received {
+                    details.push(Detail::IpcReceive {
+                        flow_id,
+                        sender: ThreadInfo::resolve(&map, sender),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // the kernel object of the context pointer and the symbols of the event
+    fn context(&self, map: &KernelObjectMap, event: &Event) -> EventContext {
+        let common = event.event_common();
+        let object = map.get(&KobjAddr::masked(common.ctx));
+
+        // user addresses are resolved with the ELF of the task the thread runs in
+        let task_name = match object {
+            Some(KernelObject::Thread(t)) => t.task.and_then(|a| map.get(&a)).map(|o| o.name()),
+            _ => None,
+        };
+        let user_ip = match event {
+            Event::Exregs(ev) => Some(ev.ip),
+            Event::Vcpu(ev) => Some(ev.ip),
+            Event::Migration(ev) => Some(ev.user_ip),
+            Event::Timer(ev) => Some(ev.user_ip),
+            _ => None,
+        };
+
+        EventContext {
+            name: object.map(|o| o.name().to_string()).unwrap_or_default(),
+            dbg_id: object.and_then(|o| o.id()),
+            ip_sym: self.symbolizer.resolve(common.ip, task_name),
+            user_ip_sym: user_ip.and_then(|ip| self.symbolizer.resolve(ip, task_name)),
+        }
+    }
+
+    // the task the thread at `addr` runs in, if known
+    fn thread_task(&self, addr: KobjAddr) -> Option<KobjAddr> {
+        match self.kernel_object_map.borrow().get(&addr) {
+            Some(KernelObject::Thread(t)) => t.task,
+            _ => None,
+        }
+    }
+}
+
+fn name_object(map: &mut KernelObjectMap, ev: &NamEvent) {
+    let name = match helpers::i8_array_to_string(ev.name) {
+        Ok(n) => n,
+        Err(_) => {
+            // TODO not sure why, but sometimes when you enable IPC events there's some gibberish
+            // in some name fields
+            info!(
+                "Could not convert Nam event bytes to name string! (event nr: {}, bytes: {:?})",
+                ev.common.number, ev.name
+            );
+            "".to_string()
+        }
+    };
+
+    let pointer = if ev.thread == 0 {
+        KobjAddr::masked(ev.obj)
+    } else {
+        KobjAddr::exact(ev.obj)
+    };
+    match map.get_mut(&pointer) {
+        Some(obj) if !obj.is_destroyed() => {
+            obj.set_name(name);
+            map.set_id(pointer, DbgId(ev.id));
+        }
+
+        // the address of a destroyed object got reused without us seeing the creation of the
+        // new object
+        _ => {
+            let base = BaseKernelObject::new(DbgId(ev.id), name);
+            let new_obj = if ev.thread != 0 {
+                KernelObject::Gate(GateObject {
+                    base,
+                    thread: KobjAddr::masked(ev.thread),
+                })
+            } else {
+                KernelObject::Generic(base)
+            };
+            map.insert(pointer, new_obj);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::converter::kernel_object::{
+    BaseKernelObject, KernelObject, KernelObjectMap, KobjAddr, ThreadObject, ThreadState,
+    UNKNOWN_TID,
+};
+use crate::event::context_switch::ContextSwitchEvent;
+use crate::event::sched::SchedEvent;
+use crate::sink::{Detail, ThreadInfo};
+use log::error;
+
+/// `SchedEvent::mode`, what happened to the scheduling context
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SchedMode {
+    Save,
+    Load,
+    Invalidate,
+    Unknown,
+}
+
+impl From<u16> for SchedMode {
+    fn from(mode: u16) -> Self {
+        match mode {
+            0 => SchedMode::Save,
+            1 => SchedMode::Load,
+            2 => SchedMode::Invalidate,
+            _ => SchedMode::Unknown,
+        }
+    }
+}
+
+/// Tracks the threads of a `CONTEXTSWITCH` event. `last_sched_in` is the thread the CPU switched
+/// to last. `None` if the event refers to an object which isn't a thread.
+// TODO: this is pretty convoluted as of now and should be simplified sometime
+pub(super) fn context_switch(
+    map: &mut KernelObjectMap,
+    last_sched_in: &mut Option<ThreadObject>,
+    event: &ContextSwitchEvent,
+) -> Option<Detail> {
+    let src = KobjAddr::masked(event.common.ctx);
+    let dst = KobjAddr::masked(event.dst);
+
+    // handle thread of scheduling context
+    // src may or may not be the same as the sched context
+    let sched = KobjAddr::masked(event.from_sched);
+    let is_idle = event.from_prio == 0;
+    if let Some(o) = map.get_mut(&sched) {
+        let prio = event.from_prio;
+        let (name, state) = if is_idle {
+            (
+                format!("idle {}", event.common.cpu),
+                Some(ThreadState::Idle),
+            )
+        } else {
+            (o.name().to_string(), None)
+        };
+
+        match o {
+            KernelObject::Generic(base) => {
+                *o = KernelObject::Thread(ThreadObject {
+                    base: BaseKernelObject {
+                        name,
+                        ..base.clone()
+                    },
+                    state: state.unwrap_or(ThreadState::Runnable),
+                    prio,
+                    task: None,
+                    sched_context: None,
+                });
+            }
+            KernelObject::Thread(t) => {
+                t.prio = prio;
+                t.base.name = name;
+                if let Some(state) = state {
+                    t.state = state;
+                }
+            }
+            _ => {
+                error!("Sched switch on none thread object");
+                return None;
+            }
+        }
+    }
+    if is_idle {
+        map.set_idle(sched);
+    }
+
+    // if the src kernel object is not of type thread yet make it so
+    if let Some(o) = map.get_mut(&src)
+        && let KernelObject::Generic(base) = o
+    {
+        *o = KernelObject::Thread(ThreadObject {
+            base: base.clone(),
+            state: ThreadState::Runnable,
+            prio: event.from_prio,
+            task: None,
+            sched_context: None,
+        });
+    }
+
+    if event.from_space != 0 {
+        map.link_thread_to_task(src, KobjAddr::masked(event.from_space));
+    }
+
+    let (prev, preempted) = match map.get_mut(&src) {
+        Some(KernelObject::Thread(t)) => {
+            // last dst is not the same as this ones src, so we entered an exluded thread, which
+            // switched to another excluded thread (not traced), which switched back to some not
+            // excluded thread
+            let mut switched_out = match last_sched_in {
+                Some(last) if last.base.id != t.base.id => last.clone(),
+                _ => t.clone(),
+            };
+
+            // nothing blocked the thread before it got switched out, so it got preempted
+            let preempted = matches!(
+                switched_out.state,
+                ThreadState::Running | ThreadState::Runnable | ThreadState::Idle
+            );
+            if t.state == ThreadState::Running {
+                t.state = ThreadState::Runnable;
+            }
+            if switched_out.state == ThreadState::Running {
+                switched_out.state = ThreadState::Runnable;
+            }
+            (thread_info(map, &switched_out), preempted)
+        }
+        Some(_) => {
+            error!("sched_switch on a non thread kernel object!");
+            return None;
+        }
+        None => (ThreadInfo::resolve(map, src), true),
+    };
+
+    // if the dst kernel object is not of type thread yet make it so
+    if let Some(o) = map.get_mut(&dst)
+        && let KernelObject::Generic(base) = o
+    {
+        *o = KernelObject::Thread(ThreadObject {
+            base: base.clone(),
+            state: ThreadState::Runnable,
+            prio: 1000,
+            task: None,
+            sched_context: None,
+        });
+    }
+
+    match map.get_mut(&dst) {
+        Some(KernelObject::Thread(t)) => {
+            if t.state != ThreadState::Idle {
+                t.state = ThreadState::Running;
+            }
+            *last_sched_in = Some(t.clone());
+        }
+        Some(_) => {
+            error!("sched_switch on a non thread kernel object!");
+            return None;
+        }
+        None => (),
+    }
+
+    Some(Detail::ContextSwitch {
+        prev,
+        next: ThreadInfo::resolve(map, dst),
+        preempted,
+    })
+}
+
+// a thread which may not be in the map at the same address anymore
+fn thread_info(map: &KernelObjectMap, t: &ThreadObject) -> ThreadInfo {
+    let tid = t.base.tid();
+    let pid = map.task_pid(t.task, tid).unwrap_or(tid);
+    ThreadInfo {
+        tid,
+        pid,
+        name: if t.base.name.is_empty() {
+            t.base.id.map(|id| id.to_string()).unwrap_or_default()
+        } else {
+            t.base.name.clone()
+        },
+        task: map.task_label(pid),
+        state: Some(t.state),
+        prio: Some(t.prio),
+    }
+}
+
+/// Updates the scheduling context of a `SCHED` event and returns the budget and priority changes
+/// derived from it
+pub(super) fn sched_context(map: &mut KernelObjectMap, event: &SchedEvent) -> Vec<Detail> {
+    let owner = KobjAddr::masked(event.owner);
+    let mode = SchedMode::from(event.mode);
+
+    let tid = match map.get_mut(&owner) {
+        Some(KernelObject::Thread(t)) => {
+            if mode == SchedMode::Load {
+                t.sched_context = Some(event.id);
+            }
+            t.base.tid()
+        }
+        Some(o) => o.tid(),
+        None => UNKNOWN_TID,
+    };
+
+    let mut details = Vec::new();
+    let sc = map.sched_context_mut(owner, event.id);
+    let prio_change = (sc.events > 0 && sc.prio != event.prio).then(|| {
+        sc.prio_changes += 1;
+        Detail::SchedPrioChange {
+            tid,
+            sc_id: event.id,
+            old_prio: sc.prio,
+            new_prio: event.prio,
+        }
+    });
+    sc.events += 1;
+    sc.prio = event.prio;
+    sc.quantum = event.quantum;
+    sc.left = event.left;
+
+    let budget = |expired| Detail::SchedBudget {
+        tid,
+        sc_id: event.id,
+        prio: event.prio,
+        quantum: event.quantum,
+        left: event.left,
+        expired,
+    };
+    match mode {
+        SchedMode::Load => sc.loads += 1,
+        SchedMode::Save if event.left <= 0 => {
+            sc.exhaustions += 1;
+            details.push(budget(true));
+        }
+        SchedMode::Save => {
+            sc.preemptions += 1;
+            sc.min_left = Some(sc.min_left.map_or(event.left, |m| m.min(event.left)));
+            details.push(budget(false));
+        }
+        SchedMode::Invalidate | SchedMode::Unknown => (),
+    }
+    details.extend(prio_change);
+    details
+}
@@ -5,15 +5,18 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "BP"]
 #[br(little)]
 pub struct BpEvent {
     pub common: EventCommon,
 
+    #[serde(skip)]
     pub __pre_pad: [i8; 2],
     pub address: u64,
     pub len: i32,
+    #[serde(skip)]
     pub __pad_1: [i8; 4],
     pub value: u64,
     pub mode: i32,
@@ -1,8 +1,9 @@
 /* Note, automatically generated from Fiasco binary */
 
 use binrw::BinRead;
+use serde::Serialize;
 
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize)]
 #[br(little)]
 pub struct EventCommon {
     pub number: u64,
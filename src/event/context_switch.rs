@@ -5,12 +5,14 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "CONTEXTSWITCH"]
 #[br(little)]
 pub struct ContextSwitchEvent {
     pub common: EventCommon,
 
+    #[serde(skip)]
     pub __pre_pad: [i8; 2],
     pub dst: u64,
     pub dst_orig: u64,
@@ -5,12 +5,14 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "DRQ"]
 #[br(little)]
 pub struct DrqEvent {
     pub common: EventCommon,
 
+    #[serde(skip)]
     pub __pre_pad: [i8; 2],
     pub func: u64,
     pub thread: u64,
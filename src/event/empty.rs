@@ -5,7 +5,8 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "EMPTY"]
 #[br(little)]
 pub struct EmptyEvent {
@@ -5,12 +5,14 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "EXREGS"]
 #[br(little)]
 pub struct ExregsEvent {
     pub common: EventCommon,
 
+    #[serde(skip)]
     pub __pre_pad: [i8; 2],
     pub id: u64,
     pub ip: u64,
@@ -5,12 +5,14 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "FULLSIZE"]
 #[br(little)]
 pub struct FullsizeEvent {
     pub common: EventCommon,
 
+    #[serde(serialize_with = "crate::helpers::serialize_c_string")]
     pub padding: [i8; 80], 
 }
 
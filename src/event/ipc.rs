@@ -5,11 +5,13 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize)]
 #[br(little)]
 pub struct IpcEvent {
     pub common: EventCommon,
 
+    #[serde(skip)]
     pub __pre_pad: [i8; 2],
     pub tag: u64,
     pub dword: [u64; 2], 
@@ -17,6 +19,7 @@ pub struct IpcEvent {
     pub dbg_id: u64,
     pub label: u64,
     pub timeout: u32,
+    #[serde(skip)]
     pub __pad_1: [i8; 4],
     pub to_abs_rcv: u64,
 }
@@ -5,10 +5,12 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize)]
 #[br(little)]
 pub struct KeBinEvent {
     pub common: EventCommon,
 
+    #[serde(serialize_with = "crate::helpers::serialize_c_string")]
     pub msg: [i8; 80],
 }
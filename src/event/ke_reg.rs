@@ -5,11 +5,14 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize)]
 #[br(little)]
 pub struct KeRegEvent {
     pub common: EventCommon,
+    #[serde(skip)]
     pub __pre_pad: [i8; 2],
     pub v: [u64; 3],
+    #[serde(serialize_with = "crate::helpers::serialize_c_string")]
     pub msg: [i8; 56],
 }
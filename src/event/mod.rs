@@ -42,8 +42,11 @@ use super::event::{
 use crate::parser::error;
 use binrw::BinRead;
 use num_enum::TryFromPrimitiveError;
+use serde::Serialize;
 
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+/// Serialized as the payload of the event, without its type
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(untagged)]
 pub enum Event {
     Drq(DrqEvent),
     Vcpu(VcpuEvent),
@@ -5,7 +5,8 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "SCHED"]
 #[br(little)]
 pub struct SchedEvent {
@@ -15,6 +16,7 @@ pub struct SchedEvent {
     pub owner: u64,
     pub id: u16,
     pub prio: u16,
+    #[serde(skip)]
     pub __pad_1: [i8; 4],
     pub left: i64,
     pub quantum: u64,
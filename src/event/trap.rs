@@ -5,15 +5,18 @@ use ctf_macros::CtfEventClass;
 
 use super::common::EventCommon;
 use binrw::BinRead;
-#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]
+use serde::Serialize;
+#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]
 #[event_name = "TRAP"]
 #[br(little)]
 pub struct TrapEvent {
     pub common: EventCommon,
 
     pub trapno: i8,
+    #[serde(skip)]
     pub __pad_1: [i8; 1],
     pub error: u16,
+    #[serde(skip)]
     pub __pad_2: [i8; 6],
     pub rbp: u64,
     pub cr2: u64,
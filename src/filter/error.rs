@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("expected <key>=<value>[,<value>...], got '{0}'")]
+    Syntax(String),
+    #[error("unknown filter key '{0}' (type, cpu, thread, task or time)")]
+    Key(String),
+    #[error("invalid CPU '{0}'")]
+    Cpu(String),
+    #[error("invalid time range '{0}', expected <start>..<end> in clock cycles")]
+    Time(String),
+}
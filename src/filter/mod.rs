@@ -0,0 +1,203 @@
+//! Selection of the events written to the trace and handed to the sinks.
+//!
+//! `--include` and `--exclude` take a condition `<key>=<value>[,<value>...]`, the event matches
+//! if it matches any of the values:
+//!
+//! - `type`: event type, as the name of the event (`IPC`, `NAM`, `FACTORY`, ...) or of its tbuf
+//!   type (`KOBJECTNAMES`, `RCUIDLE`, ...), case insensitive
+//! - `cpu`: CPU number or range of them (`0-3`)
+//! - `thread`: name or debug id (decimal or `0x` hex) of the thread the event happened on
+//! - `task`: name or debug id of the task of that thread
+//! - `time`: range `<start>..<end>` of timestamps in clock cycles, either end may be omitted
+//!
+//! An event is kept if it matches an include condition of every key used in includes, and no
+//! exclude condition. E.g. `--include type=ipc,ipcres --include cpu=0 --exclude task=moe` keeps
+//! the IPC events on CPU 0 which don't happen in moe.
+//!
+//! The [`Enricher`](crate::enrich::Enricher) tracks the kernel objects before it asks the
+//! filter, so filtered `NAM` or `FACTORY` events still name and create the objects of the events
+//! that are kept. The IPC latencies and flows only pair the kept events, a kept `IPCRES` whose
+//! `IPC` was filtered is reported without its IPC. Filtered context switches still go to the
+//! sinks which follow the threads on the CPUs, see
+//! [`TraceSink::left_out`](crate::sink::TraceSink::left_out).
+
+pub mod error;
+
+use crate::converter::kernel_object::{KernelObjectMap, KobjAddr};
+use crate::event::Event;
+use crate::event::event_type::EventType;
+use crate::sink::ThreadInfo;
+use error::Error;
+use std::cell::{Cell, OnceCell};
+use std::mem;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// A kernel object given by name or debug id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectRef {
+    Id(i64),
+    Name(String),
+}
+
+impl ObjectRef {
+    fn matches(&self, id: i64, name: &str) -> bool {
+        match self {
+            Self::Id(i) => *i == id,
+            Self::Name(n) => n == name,
+        }
+    }
+}
+
+impl From<&str> for ObjectRef {
+    fn from(value: &str) -> Self {
+        let id = match value.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        };
+        match id {
+            Some(id) => Self::Id(id),
+            None => Self::Name(value.to_string()),
+        }
+    }
+}
+
+/// A single `--include` or `--exclude` condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// Upper case names of events or tbuf types
+    Type(Vec<String>),
+    Cpu(Vec<RangeInclusive<u8>>),
+    Thread(Vec<ObjectRef>),
+    Task(Vec<ObjectRef>),
+    Time(RangeInclusive<u64>),
+}
+
+impl FromStr for Condition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, raw) = match s.split_once('=') {
+            Some((key, raw)) if !raw.is_empty() => (key, raw),
+            _ => return Err(Error::Syntax(s.to_string())),
+        };
+        let values = raw.split(',');
+        match key {
+            "type" => Ok(Self::Type(values.map(str::to_uppercase).collect())),
+            "cpu" => values
+                .map(parse_cpus)
+                .collect::<Result<_, _>>()
+                .map(Self::Cpu),
+            "thread" => Ok(Self::Thread(values.map(ObjectRef::from).collect())),
+            "task" => Ok(Self::Task(values.map(ObjectRef::from).collect())),
+            "time" => parse_time(raw).map(Self::Time),
+            _ => Err(Error::Key(key.to_string())),
+        }
+    }
+}
+
+impl Condition {
+    fn matches(&self, event: &Event, thread: &dyn Fn() -> ThreadInfo) -> bool {
+        let common = event.event_common();
+        match self {
+            Self::Type(names) => names.iter().any(|n| is_type(event, n)),
+            Self::Cpu(ranges) => ranges.iter().any(|r| r.contains(&common.cpu)),
+            Self::Thread(objects) => {
+                let thread = thread();
+                objects.iter().any(|o| o.matches(thread.tid, &thread.name))
+            }
+            Self::Task(objects) => {
+                let thread = thread();
+                objects.iter().any(|o| o.matches(thread.pid, &thread.task))
+            }
+            Self::Time(range) => range.contains(&common.tsc),
+        }
+    }
+
+    fn key(&self) -> mem::Discriminant<Self> {
+        mem::discriminant(self)
+    }
+}
+
+/// Whether `name` (upper case) is the name of `event` or of its tbuf type
+pub fn is_type(event: &Event, name: &str) -> bool {
+    event.to_string() == name
+        || EventType::try_from(event.event_common().type_).is_ok_and(|t| t.to_string() == name)
+}
+
+fn parse_cpus(value: &str) -> Result<RangeInclusive<u8>, Error> {
+    let cpu = |v: &str| v.parse::<u8>().map_err(|_| Error::Cpu(value.to_string()));
+    match value.split_once('-') {
+        Some((first, last)) => Ok(cpu(first)?..=cpu(last)?),
+        None => cpu(value).map(|c| c..=c),
+    }
+}
+
+fn parse_time(value: &str) -> Result<RangeInclusive<u64>, Error> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| Error::Time(value.to_string()))?;
+    let bound = |v: &str, default| {
+        if v.is_empty() {
+            Ok(default)
+        } else {
+            v.parse::<u64>().map_err(|_| Error::Time(value.to_string()))
+        }
+    };
+    Ok(bound(start, 0)?..=bound(end, u64::MAX)?)
+}
+
+/// The include and exclude conditions, and the number of events they filtered out
+#[derive(Debug, Default)]
+pub struct EventFilter {
+    include: Vec<Condition>,
+    exclude: Vec<Condition>,
+    filtered: Cell<u64>,
+}
+
+impl EventFilter {
+    pub fn new(include: Vec<Condition>, exclude: Vec<Condition>) -> Self {
+        Self {
+            include,
+            exclude,
+            filtered: Cell::new(0),
+        }
+    }
+
+    /// True if every event is kept
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether `event` goes to the trace and the sinks, given the kernel objects after the
+    /// enricher tracked it. Counts the events which don't.
+    pub fn keep(&self, event: &Event, map: &KernelObjectMap) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let addr = KobjAddr::masked(event.event_common().ctx);
+        let resolved = OnceCell::new();
+        let thread = || {
+            resolved
+                .get_or_init(|| ThreadInfo::resolve(map, addr))
+                .clone()
+        };
+        let included = self.include.iter().all(|c| {
+            self.include
+                .iter()
+                .filter(|other| other.key() == c.key())
+                .any(|other| other.matches(event, &thread))
+        });
+        let keep = included && !self.exclude.iter().any(|c| c.matches(event, &thread));
+        if !keep {
+            self.filtered.set(self.filtered.get() + 1);
+        }
+        keep
+    }
+
+    /// Number of events filtered out so far
+    pub fn filtered(&self) -> u64 {
+        self.filtered.get()
+    }
+}
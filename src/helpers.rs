@@ -1,4 +1,5 @@
 use core::str;
+use serde::Serializer;
 use std::str::Utf8Error;
 
 pub fn i8_array_to_string<const N: usize>(array: [i8; N]) -> Result<String, Utf8Error> {
@@ -13,3 +14,22 @@ pub fn i8_array_to_string<const N: usize>(array: [i8; N]) -> Result<String, Utf8
 
     String::from_utf8(bytes).map_err(|e| e.utf8_error())
 }
+
+/// Serializes a char array as string up to its NUL, with the bytes which aren't printable escaped
+pub fn serialize_c_string<S: Serializer, const N: usize>(
+    array: &[i8; N],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match i8_array_to_string(*array) {
+        Ok(s) => serializer.serialize_str(&s),
+        Err(_) => {
+            let end = array.iter().position(|&c| c == 0).unwrap_or(array.len());
+            let s: String = array[..end]
+                .iter()
+                .flat_map(|&c| (c as u8).escape_ascii())
+                .map(char::from)
+                .collect();
+            serializer.serialize_str(&s)
+        }
+    }
+}
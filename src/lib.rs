@@ -0,0 +1,46 @@
+//! Decoder of the Fiasco trace buffer (tbuf) events and their conversion to LTTng-like CTF
+//! streams.
+//!
+//! - [`EventParser`] decodes the raw 128 byte events into [`Event`]s
+//! - the [`Enricher`](enrich::Enricher) tracks the kernel objects ([`KernelObjectMap`]) and
+//!   derives the names, symbols, context switches and IPC flows of every event
+//! - [`TraceSink`]s get the enriched events, the CTF writer as well as the other outputs and
+//!   analyses
+//! - [`Conversion`] puts it all together, from the input to the merged trace
+//!
+//! The `l4re_tracestream` binary is a thin wrapper around [`Conversion`] creating the sinks from
+//! its options. With the `plugin` feature, the library also has the `source.fiasco.tbuf`
+//! component of the babeltrace2 plugin in `plugin/`, see `bt_plugin`. The `perfetto`, `parquet` and
+//! `sqlite` features add the export sinks of the same name.
+
+pub mod analysis;
+#[cfg(feature = "plugin")]
+pub mod bt_plugin;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod conversion;
+pub mod converter;
+#[cfg(feature = "sqlite")]
+pub mod database;
+pub mod enrich;
+pub mod event;
+pub mod filter;
+pub mod helpers;
+pub mod metadata;
+pub mod names;
+pub mod opts;
+pub mod parser;
+#[cfg(feature = "perfetto")]
+pub mod perfetto;
+pub mod sampling;
+pub mod sink;
+pub mod symbols;
+
+pub use conversion::{Conversion, Input, Summary};
+pub use converter::kernel_object::{
+    DbgId, KernelObject, KernelObjectMap, KobjAddr, KobjType, ThreadState,
+};
+pub use event::Event;
+pub use event::event_type::EventType;
+pub use parser::EventParser;
+pub use sink::{SinkEvent, TraceSink};
@@ -1,36 +1,60 @@
-mod converter;
-mod event;
-mod helpers;
-mod opts;
-mod parser;
-
-use crate::converter::interruptor::Interruptor;
-use crate::event::Event;
-use babeltrace2_sys::RunStatus;
+use babeltrace2_sys::source_plugin_descriptors;
 use clap::Parser;
-use converter::Converter;
-use converter::kernel_object::KernelObject;
-use core::str;
-use log::warn;
+use l4re_tracestream::analysis::ipc_graph::IpcGraph;
+use l4re_tracestream::analysis::sched_stats::{SchedStats, StatsFormat};
+#[cfg(feature = "parquet")]
+use l4re_tracestream::columnar::ParquetExport;
+use l4re_tracestream::conversion::Conversion;
+use l4re_tracestream::converter::TrcPlugin;
+use l4re_tracestream::converter::interruptor::Interruptor;
+use l4re_tracestream::converter::kernel_object::ObjectPreset;
+#[cfg(feature = "sqlite")]
+use l4re_tracestream::database::Database;
+use l4re_tracestream::names;
+use l4re_tracestream::opts::Opts;
+#[cfg(feature = "perfetto")]
+use l4re_tracestream::perfetto::PerfettoTrace;
+use l4re_tracestream::sink::TraceSink;
+use l4re_tracestream::sink::dump::EventDump;
+use l4re_tracestream::symbols::Symbolizer;
 use log::{debug, error, info};
-use opts::Opts;
-use parser::EventParser;
-use regex::Regex;
-use std::cell::{Cell, RefCell};
-use std::collections::{HashMap, VecDeque};
-use std::io::{self, BufReader, Cursor, Read, Write};
-use std::net::TcpListener;
-use std::path::{Path, PathBuf};
+use std::cell::RefCell;
+use std::fmt::Write;
 use std::rc::Rc;
-use std::sync::mpsc;
-use std::time::Instant;
-use std::{fs, thread};
+use std::{fs, io};
+
+// the converter pipelines load their source and the ctf sink from the plugins linked into the
+// binary
+source_plugin_descriptors!(TrcPlugin);
+
+pub mod utils_plugin_descriptors {
+    use babeltrace2_sys::ffi::*;
+
+    #[link(
+        name = "babeltrace-plugin-utils",
+        kind = "static",
+        modifiers = "+whole-archive"
+    )]
+    unsafe extern "C" {
+        pub static __bt_plugin_descriptor_auto_ptr: *const __bt_plugin_descriptor;
+    }
+}
+
+pub mod ctf_plugin_descriptors {
+    use babeltrace2_sys::ffi::*;
 
-const IP_ADDRESS: &str = "0.0.0.0:8888";
+    #[link(
+        name = "babeltrace-plugin-ctf",
+        kind = "static",
+        modifiers = "+whole-archive"
+    )]
+    unsafe extern "C" {
+        pub static __bt_plugin_descriptor_auto_ptr: *const __bt_plugin_descriptor;
+    }
+}
 
 fn main() {
     let opts = Opts::parse();
-    let opts_c = opts.clone();
 
     env_logger::init();
 
@@ -54,257 +78,144 @@ fn main() {
     })
     .unwrap();
 
-    // network -> parser
-    let (net_tx, parser_rx) = mpsc::channel();
-    // parser -> converter
-    let (parser_tx, converter_rx) = mpsc::channel::<Event>();
-
-    // Receive the event bytes from the network and pass them to the parser
-    let network_handle = thread::spawn(move || {
-        info!("Listening on {}", IP_ADDRESS);
-        let listener = TcpListener::bind(IP_ADDRESS).unwrap_or_else(|_| {
-            error!("Could not bind to provided address/port!");
+    let presets: Vec<ObjectPreset> = match &opts.names {
+        Some(path) => names::load(path).unwrap_or_else(|e| {
+            error!("Could not load names file {:?} ({})", path, e);
+            panic!();
+        }),
+        None => Vec::new(),
+    };
+    info!("Loaded {} object names", presets.len());
+
+    let symbolizer =
+        Symbolizer::load(opts.symbols.as_deref(), &opts.task_symbols).unwrap_or_else(|e| {
+            error!("Could not load symbols ({})", e);
             panic!();
         });
-        let mut events_received: u64 = 0;
-        let mut start_time: Option<Instant> = None;
-
-        match listener.accept() {
-            Ok((stream, addr)) => {
-                println!("Accepted connection from {:?}", addr);
-                let mut reader = BufReader::new(stream);
-                let mut buf: [u8; 128] = [0; 128];
-
-                while reader.read_exact(&mut buf).is_ok() {
-                    if start_time.is_none() {
-                        start_time = Some(Instant::now());
-                    }
-                    events_received += 1;
-                    match net_tx.send(buf) {
-                        Ok(_) => debug!("Parsed and sent event"),
-                        Err(e) => {
-                            info!("Parser channel closed ({})", e);
-                            break;
-                        }
-                    }
-                }
-            }
-            Err(e) => error!("Error accepting TCP connection ({:?})", e),
-        }
-
-        let runtime = start_time.unwrap().elapsed();
-        let throughput = ((events_received * 128) as f64) / runtime.as_secs_f64();
-        throughput
-    });
-
-    // Parse the event bytes and pass the to the converter
-    let parser_handle = thread::spawn(move || {
-        let mut first_event_observed = false;
-        let mut biggest_event_num: u64 = 0;
-        let mut start_time: Option<Instant> = None;
-        let mut dropped_events_total: u64 = 0;
-
-        while let Ok(event_bytes) = parser_rx.recv() {
-            if start_time.is_none() {
-                start_time = Some(Instant::now());
-            }
 
-            debug!("Received event bytes");
-            let mut reader = Cursor::new(event_bytes);
-            let event = EventParser::next_event(&mut reader);
-
-            match event {
-                Ok(event) => {
-                    if let Some(e) = event {
-                        let event_number = e.event_common().number;
-                        debug!("Event count: {event_number}");
-                        if event_number > biggest_event_num || !first_event_observed {
-                            let event_diff = if first_event_observed {
-                                event_number - biggest_event_num
-                            } else {
-                                0
-                            };
-                            if event_diff > 1 {
-                                dropped_events_total += event_diff - 1;
-                                warn!(
-                                    "Dropped {} events (event num: {event_number}, biggest event num: {biggest_event_num}",
-                                    event_diff - 1
-                                );
-                            }
-
-                            biggest_event_num = event_number;
-                            first_event_observed = true;
-                            match parser_tx.send(e) {
-                                Ok(_) => debug!("Parsed and sent event"),
-                                Err(e) => {
-                                    info!("Converter channel closed ({})", e);
-                                    break;
-                                }
-                            }
-                        } else {
-                            info!(
-                                "Found duplicate/out of order event (event nr: {event_number}, max nr: {biggest_event_num}"
-                            );
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!("Could not parse event ({:?})", e);
-                }
-            }
-        }
-
-        (start_time, dropped_events_total)
-    });
-
-    // Convert the events to CTF and pass the to the disk writer and live streamer
-    let converter_handle = thread::spawn(move || {
-        // because babeltrace only has a file system ctf sink, but we don't want to read the
-        // data in again from disk to send it to the live session
-        let eof_signal: Rc<Cell<bool>> = Rc::new(Cell::new(false));
-        let mut converters: HashMap<u8, Converter> = HashMap::new();
-        // TODO put the event stream in the converter and make a func to return it
-        let mut event_streams: HashMap<u8, Rc<RefCell<VecDeque<Event>>>> = HashMap::new();
-        let kernel_object_map: Rc<RefCell<HashMap<u64, KernelObject>>> =
-            Rc::new(RefCell::new(HashMap::new()));
-        let mut nr_conv_events: u64 = 0;
-
-        while let Ok(event) = converter_rx.recv() {
-            let cpu_id = event.event_common().cpu;
-            converters.entry(cpu_id).or_insert_with(|| {
-                let event_buf: Rc<RefCell<VecDeque<Event>>> =
-                    Rc::new(RefCell::new(VecDeque::new()));
-                event_streams.insert(cpu_id, event_buf.clone());
-
-                let mut opts_c = opts.clone();
-                opts_c.output = format!("{}_{cpu_id}", opts_c.output.to_str().unwrap()).into(); // TODO unwrap
-                debug!("Instantiating converter {cpu_id}");
-                Converter::new(
-                    event_buf,
-                    eof_signal.clone(),
-                    opts_c,
-                    cpu_id,
-                    intr.clone(),
-                    kernel_object_map.clone(),
-                )
-                .unwrap_or_else(|_| {
-                    error!("Could not instantiate converter!");
-                    panic!();
-                })
-            });
-
-            let conv = converters.get_mut(&cpu_id).unwrap();
-
-            debug!("Received event \n {:?}", event);
-            {
-                event_streams
-                    .get_mut(&cpu_id)
-                    .unwrap()
-                    .borrow_mut()
-                    .push_back(event);
-            }
-            debug!("Trying to convert event...");
-            match conv.convert_once() {
-                Ok(s) => {
-                    debug!("Succesfully converted event");
-                    nr_conv_events += 1;
-
-                    if s == RunStatus::End {
-                        break;
-                    }
-                }
-                Err(e) => error!("Error converting event ({:?})", e),
-            }
-        }
-
-        eof_signal.set(true);
-        for conv in converters.values_mut() {
-            match conv.convert() {
-                Ok(_) => debug!("Succesfully closed converter stream"),
-                Err(e) => error!("Error closing converter stream ({:?})", e),
-            }
-        }
+    let ipc_graph = Rc::new(RefCell::new(IpcGraph::new(opts.ipc_graph_nodes)));
+    let sched_stats = Rc::new(RefCell::new(SchedStats::new(opts.clock_frequency)));
+    let mut sinks: Vec<Rc<RefCell<dyn TraceSink>>> = Vec::new();
+    if opts.ipc_graph {
+        sinks.push(ipc_graph.clone());
+    }
+    if opts.sched_stats.is_some() {
+        sinks.push(sched_stats.clone());
+    }
+    #[cfg(feature = "perfetto")]
+    if let Some(path) = &opts.perfetto {
+        let trace = PerfettoTrace::create(path, opts.clock_frequency).unwrap_or_else(|e| {
+            error!("Could not create Perfetto trace {:?} ({})", path, e);
+            panic!();
+        });
+        sinks.push(Rc::new(RefCell::new(trace)));
+    }
+    if let Some(format) = opts.format {
+        let path = opts.format_output.as_deref();
+        let dump = EventDump::create(format, path, opts.clock_frequency).unwrap_or_else(|e| {
+            error!("Could not create event dump {:?} ({})", path, e);
+            panic!();
+        });
+        sinks.push(Rc::new(RefCell::new(dump)));
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dir) = &opts.parquet {
+        let export = ParquetExport::create(dir).unwrap_or_else(|e| {
+            error!("Could not create Parquet directory {:?} ({})", dir, e);
+            panic!();
+        });
+        sinks.push(Rc::new(RefCell::new(export)));
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = &opts.sqlite {
+        let db = Database::create(path, opts.clock_frequency).unwrap_or_else(|e| {
+            error!("Could not create SQLite database {:?} ({})", path, e);
+            panic!();
+        });
+        sinks.push(Rc::new(RefCell::new(db)));
+    }
 
-        // retrun the cpus of which we saw events, so we can merge those streams later
-        (converters.into_keys().collect::<Vec<u8>>(), nr_conv_events)
+    let conversion = Conversion::from_opts(&opts)
+        .interruptor(intr)
+        .presets(presets)
+        .symbolizer(symbolizer);
+    let conversion = sinks.into_iter().fold(conversion, Conversion::sink);
+    let summary = conversion.run().unwrap_or_else(|e| {
+        error!("Conversion failed ({})", e);
+        panic!();
     });
 
-    let rcv_throughput = network_handle.join().unwrap();
-    let (start_time, dropped_events) = parser_handle.join().unwrap();
-    let (cpus, conv_events) = converter_handle.join().unwrap();
-
-    println!("EVENTS TOTAL: {conv_events}");
-    if let Some(start) = start_time {
+    let mut report = String::new();
+    let _ = writeln!(report, "EVENTS TOTAL: {}", summary.events);
+    if let Some(start) = summary.start_time {
         let runtime = start.elapsed();
-        let throughput = (conv_events as f64) / runtime.as_secs_f64();
-        println!("THROUGHPUT: {throughput} (EVENTS/SEC)");
+        let throughput = (summary.events as f64) / runtime.as_secs_f64();
+        let _ = writeln!(report, "THROUGHPUT: {throughput} (EVENTS/SEC)");
     } else {
         error!("Start time is None!");
     }
-    println!("EVENTS DROPPED: {dropped_events}");
-    println!("RECEIVE THROUHGPUT: {rcv_throughput}");
-    println!("NR CPUS: {}", cpus.len());
-
-    merge_traces(cpus, opts_c.output).unwrap();
-}
-
-fn merge_traces(cpus: Vec<u8>, path: PathBuf) -> Result<(), io::Error> {
-    // Define input directories and output
-    let out_dir = Path::new(&path);
-
-    // Create output directory if it doesn't exist
-    if !out_dir.exists() {
-        fs::create_dir(out_dir)?;
+    let _ = writeln!(report, "EVENTS DROPPED: {}", summary.dropped_events);
+    if summary.filtered_events > 0 {
+        let _ = writeln!(report, "EVENTS FILTERED: {}", summary.filtered_events);
     }
-
-    // Move and rename stream files
-    for cpu in &cpus {
-        let src_dir = PathBuf::from(format!("{}_{}", path.display(), cpu));
-        let stream_file = src_dir.join("stream");
-        let dest_file = out_dir.join(format!("stream_{}", cpu));
-        fs::copy(&stream_file, &dest_file)?;
+    for (event_type, discarded) in &summary.discarded_events {
+        let _ = writeln!(report, "EVENTS DISCARDED ({event_type}): {discarded}");
+    }
+    let _ = writeln!(report, "RECEIVE THROUHGPUT: {}", summary.receive_throughput);
+    let _ = writeln!(report, "NR CPUS: {}", summary.cpus.len());
+    report.push_str(&summary.ipc_latency.summary());
+
+    // nothing created the output directory for the files written next to the trace
+    let writes_output = opts.object_table.is_some()
+        || opts.ipc_graph
+        || opts.sched_stats.is_some_and(|f| f != StatsFormat::Table);
+    if opts.no_ctf
+        && writes_output
+        && let Err(e) = fs::create_dir_all(&opts.output)
+    {
+        error!(
+            "Could not create output directory {:?} ({})",
+            opts.output, e
+        );
     }
 
-    // Merge metadata files
-    let mut merged = String::new();
-    let mut seen_sections = Vec::new();
-    let section_re = Regex::new(r"^(trace|env|clock) \{").unwrap();
-    let mut include_lines = true;
-
-    for cpu in cpus {
-        let meta_path = PathBuf::from(format!("{}_{}/metadata", path.display(), cpu));
-        let mut file = fs::File::open(&meta_path)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-
-        for line in content.lines() {
-            if let Some(cap) = section_re.captures(line) {
-                let sec = cap.get(1).unwrap().as_str();
-                if seen_sections.contains(&sec.to_string()) {
-                    // Skip lines until matching closing brace
-                    include_lines = false;
-                } else {
-                    seen_sections.push(sec.to_string());
-                    include_lines = true;
-                }
-            }
-
-            if include_lines {
-                merged.push_str(line);
-                merged.push('\n');
-            }
+    if let Some(format) = opts.object_table {
+        let path = opts.output.join(format.file_name());
+        let table = names::export::object_table(&summary.kernel_objects);
+        match names::export::write(&path, format, &table) {
+            Ok(_) => info!("Wrote kernel object table to {:?}", path),
+            Err(e) => error!("Could not write kernel object table ({})", e),
+        }
+    }
 
-            // Detect end of section
-            if !include_lines && line.trim() == "};" {
-                include_lines = true;
-            }
+    if opts.ipc_graph {
+        let ipc_graph = ipc_graph.take();
+        let dot_path = opts.output.join("ipc_graph.dot");
+        let json_path = opts.output.join("ipc_graph.json");
+        let res = ipc_graph
+            .to_json()
+            .map_err(io::Error::from)
+            .and_then(|json| fs::write(&json_path, json))
+            .and_then(|_| fs::write(&dot_path, ipc_graph.to_dot()));
+        match res {
+            Ok(_) => info!("Wrote IPC graph to {:?} and {:?}", dot_path, json_path),
+            Err(e) => error!("Could not write IPC graph ({})", e),
         }
     }
 
-    // Write merged metadata
-    let mut out_meta = fs::File::create(out_dir.join("metadata"))?;
-    out_meta.write_all(merged.as_bytes())?;
+    if let Some(format) = opts.sched_stats {
+        let stats = sched_stats.borrow().report(&summary.kernel_objects);
+        if format == StatsFormat::Table {
+            report.push_str(&stats.table());
+        } else if let Err(e) = stats.write(&opts.output, format) {
+            error!("Could not write scheduling statistics ({})", e);
+        }
+    }
 
-    debug!("Merged CTF streams into {:?}", out_dir);
-    Ok(())
+    // a dump without `--format-output` is written to stdout, keep it parseable
+    if opts.format.is_some() && opts.format_output.is_none() {
+        eprint!("{report}");
+    } else {
+        print!("{report}");
+    }
 }
@@ -0,0 +1,634 @@
+//! Translation of the TSDL metadata into a CTF 2 metadata stream (a JSON text sequence of
+//! fragments, each preceded by the record separator)
+//!
+//! The decoded Fiasco values which are plain integers in CTF 1.8 get richer field classes: the
+//! message tag flags of `IPC` and `IPCRES` become bit maps, the IPC error codes of `IPCRES` and
+//! the special raw timeouts of `IPC` become enumeration mappings.
+
+use super::error::Error;
+use super::tsdl::{Attributes, Field, FieldType, Length, Metadata, Value};
+use crate::converter::event::ipc_decode::{self, MSGTAG_FLAGS, SPECIAL_TIMEOUTS};
+use serde_json::{Map, Value as Json, json};
+
+const RECORD_SEPARATOR: char = '\x1e';
+
+/// Root of a field location, the scope the field class belongs to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Scope {
+    PacketHeader,
+    PacketContext,
+    EventHeader,
+    EventCommonContext,
+    EventSpecificContext,
+    EventPayload,
+}
+
+impl Scope {
+    fn origin(&self) -> &'static str {
+        match self {
+            Scope::PacketHeader => "packet-header",
+            Scope::PacketContext => "packet-context",
+            Scope::EventHeader => "event-record-header",
+            Scope::EventCommonContext => "event-record-common-context",
+            Scope::EventSpecificContext => "event-record-specific-context",
+            Scope::EventPayload => "event-record-payload",
+        }
+    }
+
+    // roles of the members of the root structure which babeltrace gives the well known names
+    fn role(&self, name: &str) -> Option<&'static str> {
+        match (self, name) {
+            (Scope::PacketHeader, "magic") => Some("packet-magic-number"),
+            (Scope::PacketHeader, "uuid") => Some("metadata-stream-uuid"),
+            (Scope::PacketHeader, "stream_id") => Some("data-stream-class-id"),
+            (Scope::PacketHeader, "stream_instance_id") => Some("data-stream-id"),
+            (Scope::PacketContext, "timestamp_begin") => Some("default-clock-timestamp"),
+            (Scope::PacketContext, "timestamp_end") => Some("packet-end-default-clock-timestamp"),
+            (Scope::PacketContext, "packet_size") => Some("packet-total-length"),
+            (Scope::PacketContext, "content_size") => Some("packet-content-length"),
+            (Scope::PacketContext, "events_discarded") => {
+                Some("discarded-event-record-counter-snapshot")
+            }
+            (Scope::PacketContext, "packet_seq_num") => Some("packet-sequence-number"),
+            (Scope::EventHeader, "id") => Some("event-record-class-id"),
+            (Scope::EventHeader, "timestamp") => Some("default-clock-timestamp"),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `metadata` as CTF 2 metadata stream
+pub fn to_json_sequence(metadata: &Metadata) -> Result<String, Error> {
+    let translator = Translator {
+        big_endian: metadata.trace.str("byte_order") == Some("be"),
+    };
+    let mut fragments = vec![
+        translator.preamble(metadata)?,
+        translator.trace_class(metadata)?,
+    ];
+    for clock in &metadata.clocks {
+        fragments.push(translator.clock_class(clock)?);
+    }
+    for stream in &metadata.streams {
+        fragments.push(translator.data_stream_class(stream)?);
+    }
+    for event in &metadata.events {
+        fragments.push(translator.event_record_class(event)?);
+    }
+
+    let mut out = String::new();
+    for fragment in fragments {
+        out.push(RECORD_SEPARATOR);
+        out.push_str(&serde_json::to_string_pretty(&fragment).unwrap_or_default());
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// the CTF 1.8 names of babeltrace are prefixed with `_` if they could clash with a keyword
+fn unprotect(name: &str) -> &str {
+    name.strip_prefix('_').unwrap_or(name)
+}
+
+fn uuid_bytes(uuid: &str) -> Result<Vec<u8>, Error> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return Err(Error::Unexpected(format!("UUID {uuid}")));
+    }
+    (0..16)
+        .map(|i| {
+            u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| Error::Unexpected(format!("UUID {uuid}")))
+        })
+        .collect()
+}
+
+struct Translator {
+    // byte order of the trace, `native` in the field classes refers to it
+    big_endian: bool,
+}
+
+impl Translator {
+    fn preamble(&self, metadata: &Metadata) -> Result<Json, Error> {
+        let mut fragment = json!({ "type": "preamble", "version": 2 });
+        if let Some(uuid) = metadata.trace.str("uuid") {
+            fragment["uuid"] = json!(uuid_bytes(uuid)?);
+        }
+        Ok(fragment)
+    }
+
+    fn trace_class(&self, metadata: &Metadata) -> Result<Json, Error> {
+        let mut environment = Map::new();
+        for (key, value) in &metadata.env.0 {
+            let value = match value {
+                Value::Int(i) => json!(i),
+                v => json!(v.as_str().unwrap_or_default()),
+            };
+            environment.insert(key.clone(), value);
+        }
+
+        let mut fragment = json!({ "type": "trace-class", "environment": environment });
+        if let Some(header) = metadata.trace.field_type("packet.header") {
+            fragment["packet-header-field-class"] =
+                self.field_class(header, Scope::PacketHeader, &[])?;
+        }
+        Ok(fragment)
+    }
+
+    fn clock_class(&self, clock: &Attributes) -> Result<Json, Error> {
+        let name = clock.str("name").ok_or(Error::Missing("clock name"))?;
+        let mut fragment = json!({
+            "type": "clock-class",
+            "id": name,
+            "name": name,
+            "frequency": clock.int("freq").unwrap_or(1_000_000_000),
+            "offset-from-origin": {
+                "seconds": clock.int("offset_s").unwrap_or(0),
+                "cycles": clock.int("offset").unwrap_or(0),
+            },
+        });
+        if let Some(precision) = clock.int("precision") {
+            fragment["precision"] = json!(precision);
+        }
+        if let Some(description) = clock.str("description") {
+            fragment["description"] = json!(description);
+        }
+        if clock.get("absolute").and_then(Value::as_bool) == Some(true) {
+            fragment["origin"] = json!("unix-epoch");
+        }
+        Ok(fragment)
+    }
+
+    fn data_stream_class(&self, stream: &Attributes) -> Result<Json, Error> {
+        let mut fragment = json!({
+            "type": "data-stream-class",
+            "id": stream.int("id").unwrap_or(0),
+        });
+        let scopes = [
+            (
+                "packet.context",
+                "packet-context-field-class",
+                Scope::PacketContext,
+            ),
+            (
+                "event.header",
+                "event-record-header-field-class",
+                Scope::EventHeader,
+            ),
+            (
+                "event.context",
+                "event-record-common-context-field-class",
+                Scope::EventCommonContext,
+            ),
+        ];
+        for (key, property, scope) in scopes {
+            if let Some(field_type) = stream.field_type(key) {
+                fragment[property] = self.field_class(field_type, scope, &[])?;
+            }
+        }
+        // the clock the event timestamps are mapped to
+        let clock = stream
+            .field_type("event.header")
+            .and_then(|header| clock_of(header, "timestamp"));
+        if let Some(clock) = clock {
+            fragment["default-clock-class-id"] = json!(clock);
+        }
+        Ok(fragment)
+    }
+
+    fn event_record_class(&self, event: &Attributes) -> Result<Json, Error> {
+        let mut fragment = json!({
+            "type": "event-record-class",
+            "id": event.int("id").unwrap_or(0),
+            "data-stream-class-id": event.int("stream_id").unwrap_or(0),
+        });
+        if let Some(name) = event.str("name") {
+            fragment["name"] = json!(name);
+        }
+        if let Some(context) = event.field_type("context") {
+            fragment["specific-context-field-class"] =
+                self.field_class(context, Scope::EventSpecificContext, &[])?;
+        }
+        if let Some(fields) = event.field_type("fields") {
+            let mut payload = self.field_class(fields, Scope::EventPayload, &[])?;
+            if let Some(name) = event.str("name") {
+                decoded_members(name, &mut payload);
+            }
+            fragment["payload-field-class"] = payload;
+        }
+        Ok(fragment)
+    }
+
+    // `path` are the names of the structure members leading to the field class
+    fn field_class(
+        &self,
+        field_type: &FieldType,
+        scope: Scope,
+        path: &[&str],
+    ) -> Result<Json, Error> {
+        match field_type {
+            FieldType::Integer(attributes) => {
+                let role = match path {
+                    [name] => scope.role(name),
+                    _ => None,
+                };
+                Ok(self.integer(attributes, role))
+            }
+            FieldType::Enum {
+                container,
+                mappings,
+            } => {
+                let FieldType::Integer(attributes) = container.as_ref() else {
+                    return Err(Error::Unsupported("enumeration container".to_string()));
+                };
+                let mut class = self.integer(attributes, None);
+                let mut labels = Map::new();
+                for m in mappings {
+                    let ranges = labels.entry(m.label.clone()).or_insert_with(|| json!([]));
+                    if let Json::Array(ranges) = ranges {
+                        ranges.push(json!([m.lower, m.upper]));
+                    }
+                }
+                class["mappings"] = Json::Object(labels);
+                Ok(class)
+            }
+            FieldType::FloatingPoint(attributes) => {
+                let length = attributes.int("exp_dig").unwrap_or(0)
+                    + attributes.int("mant_dig").unwrap_or(0);
+                Ok(json!({
+                    "type": "fixed-length-floating-point-number",
+                    "length": length,
+                    "byte-order": self.byte_order(attributes),
+                    "alignment": attributes.int("align").unwrap_or(8),
+                }))
+            }
+            FieldType::String(_) => Ok(json!({ "type": "null-terminated-string" })),
+            FieldType::Struct { fields, align } => {
+                let mut members = Vec::new();
+                for field in fields {
+                    let name = unprotect(&field.name);
+                    let mut member_path = path.to_vec();
+                    member_path.push(name);
+                    members.push(json!({
+                        "name": name,
+                        "field-class": self.member(field, scope, &member_path)?,
+                    }));
+                }
+                let mut class = json!({ "type": "structure", "member-classes": members });
+                if let Some(align) = align.filter(|a| *a > 1) {
+                    class["minimum-alignment"] = json!(align);
+                }
+                Ok(class)
+            }
+            FieldType::Variant { .. } => Err(Error::Unsupported("variant".to_string())),
+        }
+    }
+
+    // the field class of a structure member, with its array dimensions
+    fn member(&self, field: &Field, scope: Scope, path: &[&str]) -> Result<Json, Error> {
+        self.array(&field.field_type, &field.dims, scope, path)
+    }
+
+    fn array(
+        &self,
+        element: &FieldType,
+        dims: &[Length],
+        scope: Scope,
+        path: &[&str],
+    ) -> Result<Json, Error> {
+        let Some((length, inner)) = dims.split_first() else {
+            return self.field_class(element, scope, path);
+        };
+
+        let (kind, length_property, length_value) = match length {
+            Length::Static(n) => ("static-length", "length", json!(n)),
+            Length::Dynamic(name) => {
+                // the length field is a sibling of the array
+                let mut location: Vec<&str> = path[..path.len().saturating_sub(1)].to_vec();
+                location.push(unprotect(name));
+                (
+                    "dynamic-length",
+                    "length-field-location",
+                    json!({ "origin": scope.origin(), "path": location }),
+                )
+            }
+        };
+
+        // byte arrays become strings or BLOBs
+        if let (FieldType::Integer(attributes), []) = (element, inner)
+            && attributes.int("size") == Some(8)
+            && attributes.get("signed").and_then(Value::as_bool) != Some(true)
+        {
+            let is_string = matches!(attributes.str("encoding"), Some("UTF8" | "ASCII"));
+            let mut class = json!({
+                "type": format!("{kind}-{}", if is_string { "string" } else { "blob" }),
+            });
+            class[length_property] = length_value;
+            if let Some(role) = path.first().and_then(|name| scope.role(name))
+                && path.len() == 1
+            {
+                class["roles"] = json!([role]);
+            }
+            return Ok(class);
+        }
+
+        let mut class = json!({
+            "type": format!("{kind}-array"),
+            "element-field-class": self.array(element, inner, scope, path)?,
+        });
+        class[length_property] = length_value;
+        Ok(class)
+    }
+
+    fn integer(&self, attributes: &Attributes, role: Option<&str>) -> Json {
+        let signed = attributes.get("signed").and_then(Value::as_bool) == Some(true);
+        let mut class = json!({
+            "type": if signed {
+                "fixed-length-signed-integer"
+            } else {
+                "fixed-length-unsigned-integer"
+            },
+            "length": attributes.int("size").unwrap_or(8),
+            "byte-order": self.byte_order(attributes),
+            "alignment": attributes.int("align").unwrap_or(8),
+        });
+        let base = match attributes.get("base") {
+            Some(Value::Int(base)) => *base,
+            Some(Value::Ident(base)) => match base.as_str() {
+                "binary" | "b" => 2,
+                "octal" | "o" => 8,
+                "hexadecimal" | "x" | "X" | "p" => 16,
+                _ => 10,
+            },
+            _ => 10,
+        };
+        if base != 10 {
+            class["preferred-display-base"] = json!(base);
+        }
+        if let Some(role) = role {
+            class["roles"] = json!([role]);
+        }
+        class
+    }
+
+    fn byte_order(&self, attributes: &Attributes) -> &'static str {
+        let big_endian = match attributes.str("byte_order") {
+            Some("be" | "network") => true,
+            Some("le") => false,
+            _ => self.big_endian,
+        };
+        if big_endian {
+            "big-endian"
+        } else {
+            "little-endian"
+        }
+    }
+}
+
+// replaces the integer classes of the payload members holding decoded Fiasco values
+fn decoded_members(event: &str, payload: &mut Json) {
+    let Some(Json::Array(members)) = payload.get_mut("member-classes") else {
+        return;
+    };
+    for member in members {
+        let name = member["name"].as_str().unwrap_or_default().to_string();
+        let class = &mut member["field-class"];
+        match (event, name.as_str()) {
+            ("IPC" | "IPCRES", "tag_flags") => {
+                let flags: Map<_, _> = MSGTAG_FLAGS
+                    .iter()
+                    .enumerate()
+                    .map(|(bit, flag)| (flag.to_string(), json!([[bit, bit]])))
+                    .collect();
+                class["type"] = json!("fixed-length-bit-map");
+                class["flags"] = Json::Object(flags);
+                if let Json::Object(class) = class {
+                    class.remove("preferred-display-base");
+                }
+            }
+            ("IPCRES", "error_code") => {
+                let mappings: Map<_, _> = ipc_decode::errors()
+                    .map(|(code, name)| (name, json!([[code, code]])))
+                    .collect();
+                class["mappings"] = Json::Object(mappings);
+            }
+            ("IPC", "snd_timeout_raw" | "rcv_timeout_raw") => {
+                let mappings: Map<_, _> = SPECIAL_TIMEOUTS
+                    .iter()
+                    .map(|(name, lower, upper)| (name.to_string(), json!([[lower, upper]])))
+                    .collect();
+                class["mappings"] = Json::Object(mappings);
+            }
+            _ => (),
+        }
+    }
+}
+
+// name of the clock the member `name` of the structure is mapped to (`map = clock.<name>.value`)
+fn clock_of<'a>(field_type: &'a FieldType, name: &str) -> Option<&'a str> {
+    let FieldType::Struct { fields, .. } = field_type else {
+        return None;
+    };
+    let field = fields.iter().find(|f| unprotect(&f.name) == name)?;
+    let FieldType::Integer(attributes) = &field.field_type else {
+        return None;
+    };
+    attributes
+        .str("map")?
+        .strip_prefix("clock.")?
+        .strip_suffix(".value")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("testdata/ctf_fs.tsdl");
+
+    fn fragments() -> Vec<Json> {
+        let metadata = Metadata::parse(SAMPLE).unwrap();
+        let sequence = to_json_sequence(&metadata).unwrap();
+        assert!(sequence.starts_with(RECORD_SEPARATOR));
+        sequence
+            .split(RECORD_SEPARATOR)
+            .skip(1)
+            .map(|f| serde_json::from_str(f).unwrap())
+            .collect()
+    }
+
+    fn fragment(fragments: &[Json], type_: &str, name: Option<&str>) -> Json {
+        fragments
+            .iter()
+            .find(|f| f["type"] == type_ && name.is_none_or(|n| f["name"] == n))
+            .unwrap()
+            .clone()
+    }
+
+    // the field class of the structure member `name`
+    fn member<'a>(structure: &'a Json, name: &str) -> &'a Json {
+        structure["member-classes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|m| m["name"] == name)
+            .map(|m| &m["field-class"])
+            .unwrap()
+    }
+
+    #[test]
+    fn fragment_order() {
+        let types: Vec<String> = fragments()
+            .iter()
+            .map(|f| f["type"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "preamble",
+                "trace-class",
+                "clock-class",
+                "data-stream-class",
+                "event-record-class",
+                "event-record-class",
+                "event-record-class",
+            ]
+        );
+    }
+
+    #[test]
+    fn roles() {
+        let fragments = fragments();
+        let preamble = fragment(&fragments, "preamble", None);
+        assert_eq!(preamble["version"], 2);
+        assert_eq!(preamble["uuid"].as_array().unwrap().len(), 16);
+        assert_eq!(preamble["uuid"][0], 0x3f);
+
+        let header = &fragment(&fragments, "trace-class", None)["packet-header-field-class"];
+        assert_eq!(
+            member(header, "magic")["roles"],
+            json!(["packet-magic-number"])
+        );
+        assert_eq!(member(header, "magic")["preferred-display-base"], 16);
+        assert_eq!(member(header, "uuid")["type"], "static-length-blob");
+        assert_eq!(member(header, "uuid")["length"], 16);
+        assert_eq!(
+            member(header, "uuid")["roles"],
+            json!(["metadata-stream-uuid"])
+        );
+        assert_eq!(
+            member(header, "stream_id")["roles"],
+            json!(["data-stream-class-id"])
+        );
+
+        let stream = fragment(&fragments, "data-stream-class", None);
+        assert_eq!(stream["default-clock-class-id"], "monotonic");
+        let context = &stream["packet-context-field-class"];
+        for (name, role) in [
+            ("packet_size", "packet-total-length"),
+            ("content_size", "packet-content-length"),
+            ("timestamp_begin", "default-clock-timestamp"),
+            ("timestamp_end", "packet-end-default-clock-timestamp"),
+            (
+                "events_discarded",
+                "discarded-event-record-counter-snapshot",
+            ),
+            ("packet_seq_num", "packet-sequence-number"),
+        ] {
+            assert_eq!(member(context, name)["roles"], json!([role]), "{name}");
+        }
+        assert!(member(context, "cpu_id").get("roles").is_none());
+        let header = &stream["event-record-header-field-class"];
+        assert_eq!(
+            member(header, "id")["roles"],
+            json!(["event-record-class-id"])
+        );
+        assert_eq!(
+            member(header, "timestamp")["roles"],
+            json!(["default-clock-timestamp"])
+        );
+
+        let clock = fragment(&fragments, "clock-class", None);
+        assert_eq!(clock["frequency"], 2_000_000_000);
+        assert!(clock.get("origin").is_none());
+    }
+
+    #[test]
+    fn payload_classes() {
+        let fragments = fragments();
+        let sched_switch = fragment(&fragments, "event-record-class", Some("sched_switch"));
+        assert_eq!(sched_switch["id"], 0);
+        let payload = &sched_switch["payload-field-class"];
+        assert_eq!(member(payload, "prev_comm")["type"], "static-length-string");
+        assert_eq!(
+            member(payload, "prev_tid")["type"],
+            "fixed-length-signed-integer"
+        );
+        assert_eq!(
+            member(payload, "prev_state")["mappings"]["TASK_DEAD"],
+            json!([[64, 127]])
+        );
+        assert_eq!(
+            member(payload, "next_comm")["type"],
+            "null-terminated-string"
+        );
+
+        // the length of a sequence is the member before it
+        let ipc = fragment(&fragments, "event-record-class", Some("IPC"));
+        let dword = member(&ipc["payload-field-class"], "dword");
+        assert_eq!(dword["type"], "dynamic-length-array");
+        assert_eq!(
+            dword["length-field-location"],
+            json!({ "origin": "event-record-payload", "path": ["_dword_len"] })
+        );
+        assert_eq!(dword["element-field-class"]["length"], 64);
+    }
+
+    #[test]
+    fn decoded_ipc_values() {
+        let fragments = fragments();
+        let ipc = fragment(&fragments, "event-record-class", Some("IPC"));
+        let ipc_res = fragment(&fragments, "event-record-class", Some("IPCRES"));
+
+        for payload in [&ipc["payload-field-class"], &ipc_res["payload-field-class"]] {
+            let flags = member(payload, "tag_flags");
+            assert_eq!(flags["type"], "fixed-length-bit-map");
+            assert_eq!(flags["length"], 8);
+            assert_eq!(
+                flags["flags"],
+                json!({
+                    "transfer_fpu": [[0, 0]],
+                    "schedule": [[1, 1]],
+                    "propagate": [[2, 2]],
+                    "error": [[3, 3]],
+                })
+            );
+        }
+
+        let error_code = member(&ipc_res["payload-field-class"], "error_code");
+        assert_eq!(error_code["type"], "fixed-length-unsigned-integer");
+        let mappings = &error_code["mappings"];
+        assert_eq!(mappings["ok"], json!([[0, 0]]));
+        assert_eq!(mappings["snd_timeout"], json!([[2, 2]]));
+        assert_eq!(mappings["rcv_timeout"], json!([[3, 3]]));
+        assert_eq!(mappings["rcv_canceled"], json!([[7, 7]]));
+        assert!(
+            member(&ipc_res["payload-field-class"], "error")
+                .get("mappings")
+                .is_none()
+        );
+
+        for name in ["snd_timeout_raw", "rcv_timeout_raw"] {
+            let timeout = member(&ipc["payload-field-class"], name);
+            assert_eq!(timeout["length"], 16);
+            assert_eq!(
+                timeout["mappings"],
+                json!({ "never": [[0, 0]], "absolute": [[0x8000, 0xffff]] })
+            );
+        }
+        assert!(
+            member(&ipc["payload-field-class"], "tag")
+                .get("mappings")
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Unexpected end of the metadata")]
+    Eof,
+    #[error("Unexpected {0} in the metadata")]
+    Unexpected(String),
+    #[error("The {0} of the metadata is not supported")]
+    Unsupported(String),
+    #[error("Missing {0} in the metadata")]
+    Missing(&'static str),
+}
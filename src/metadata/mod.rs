@@ -0,0 +1,62 @@
+//! Metadata of the written CTF trace.
+//!
+//! babeltrace's `sink.ctf.fs` writes a CTF 1.8 trace per CPU. For CTF 2 the TSDL metadata of
+//! these traces is parsed ([`tsdl`]), merged and translated into the JSON metadata of CTF 2
+//! ([`ctf2`]). The data streams are the same for both versions.
+
+pub mod ctf2;
+pub mod error;
+pub mod tsdl;
+
+use clap::ValueEnum;
+use tsdl::{Attributes, Metadata};
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CtfVersion {
+    /// CTF 1.8 with TSDL metadata
+    #[default]
+    #[value(name = "1.8")]
+    V1_8,
+    /// CTF 2 with JSON metadata, needs babeltrace2 2.1 or another CTF 2 reader
+    #[value(name = "2")]
+    V2,
+}
+
+/// Merges the metadata of the traces of all CPUs: trace and environment are the ones of the first
+/// trace, clocks, stream and event classes are collected from all of them
+pub fn merge(traces: Vec<Metadata>) -> Metadata {
+    let mut traces = traces.into_iter();
+    let mut merged = traces.next().unwrap_or_default();
+
+    for metadata in traces {
+        for clock in metadata.clocks {
+            if !merged
+                .clocks
+                .iter()
+                .any(|c| c.str("name") == clock.str("name"))
+            {
+                merged.clocks.push(clock);
+            }
+        }
+        for stream in metadata.streams {
+            if !merged
+                .streams
+                .iter()
+                .any(|s| s.int("id") == stream.int("id"))
+            {
+                merged.streams.push(stream);
+            }
+        }
+        let event_id = |e: &Attributes| (e.int("stream_id"), e.int("id"));
+        for event in metadata.events {
+            if !merged
+                .events
+                .iter()
+                .any(|e| event_id(e) == event_id(&event))
+            {
+                merged.events.push(event);
+            }
+        }
+    }
+    merged
+}
@@ -0,0 +1,525 @@
+//! Parser for the CTF 1.8 TSDL metadata as written by babeltrace's `sink.ctf.fs`
+
+use super::error::Error;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i128),
+    Str(String),
+    Punct(char),
+    // `:=`
+    TypeAssign,
+    // `...` of enum ranges
+    Ellipsis,
+}
+
+/// Value of an attribute, `key = value;` or `key := type;`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Identifier, dotted ones like `clock.monotonic.value` included
+    Ident(String),
+    Int(i128),
+    Str(String),
+    Type(FieldType),
+}
+
+impl Value {
+    pub fn as_int(&self) -> Option<i128> {
+        match self {
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Identifiers and strings
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Ident(s) | Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Int(i) => Some(*i != 0),
+            Value::Ident(s) => Some(s == "true" || s == "TRUE"),
+            _ => None,
+        }
+    }
+}
+
+/// `key = value;` statements of a block, in their order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attributes(pub Vec<(String, Value)>);
+
+impl Attributes {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn int(&self, key: &str) -> Option<i128> {
+        self.get(key).and_then(Value::as_int)
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    pub fn field_type(&self, key: &str) -> Option<&FieldType> {
+        match self.get(key) {
+            Some(Value::Type(t)) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+/// Length of an array dimension
+#[derive(Debug, Clone, PartialEq)]
+pub enum Length {
+    Static(u64),
+    /// Name of the length field of a sequence
+    Dynamic(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// The name as in the metadata, with the protecting `_` prefix
+    pub name: String,
+    pub field_type: FieldType,
+    /// Array dimensions, the outermost first
+    pub dims: Vec<Length>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumMapping {
+    pub label: String,
+    pub lower: i128,
+    pub upper: i128,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    Integer(Attributes),
+    FloatingPoint(Attributes),
+    String(Attributes),
+    Enum {
+        container: Box<FieldType>,
+        mappings: Vec<EnumMapping>,
+    },
+    Struct {
+        fields: Vec<Field>,
+        align: Option<u64>,
+    },
+    Variant {
+        tag: Option<String>,
+        fields: Vec<Field>,
+    },
+}
+
+/// The top level blocks of a metadata file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub trace: Attributes,
+    pub env: Attributes,
+    pub clocks: Vec<Attributes>,
+    pub streams: Vec<Attributes>,
+    pub events: Vec<Attributes>,
+}
+
+impl Metadata {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?.into_iter().peekable(),
+        };
+        let mut metadata = Metadata::default();
+
+        while let Some(token) = parser.tokens.next() {
+            let Token::Ident(block) = token else {
+                return Err(Error::Unexpected(format!("{token:?}")));
+            };
+            let attributes = parser.block()?;
+            parser.expect(';')?;
+            match block.as_str() {
+                "trace" => metadata.trace = attributes,
+                "env" => metadata.env = attributes,
+                "clock" => metadata.clocks.push(attributes),
+                "stream" => metadata.streams.push(attributes),
+                "event" => metadata.events.push(attributes),
+                _ => return Err(Error::Unsupported(format!("{block} block"))),
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '/' {
+            chars.next();
+            match chars.next() {
+                Some('/') => {
+                    chars.by_ref().find(|&c| c == '\n');
+                }
+                Some('*') => {
+                    let mut last = ' ';
+                    for c in chars.by_ref() {
+                        if last == '*' && c == '/' {
+                            break;
+                        }
+                        last = c;
+                    }
+                }
+                _ => return Err(Error::Unexpected("/".to_string())),
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => s.extend(chars.next()),
+                    c => s.push(c),
+                }
+            }
+            tokens.push(Token::Str(s));
+        } else if c.is_ascii_digit() {
+            let mut s = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_alphanumeric() {
+                    break;
+                }
+                s.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Int(parse_int(&s)?));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut s = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                s.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(s));
+        } else {
+            chars.next();
+            let token = match (c, chars.peek()) {
+                (':', Some('=')) => {
+                    chars.next();
+                    Token::TypeAssign
+                }
+                ('.', Some('.')) => {
+                    chars.next();
+                    chars.next();
+                    Token::Ellipsis
+                }
+                _ => Token::Punct(c),
+            };
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_int(s: &str) -> Result<i128, Error> {
+    // integer suffixes like `1U` or `2ULL`
+    let s = s.trim_end_matches(['u', 'U', 'l', 'L']);
+    let res = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        i128::from_str_radix(hex, 16)
+    } else if s.len() > 1 && s.starts_with('0') {
+        i128::from_str_radix(&s[1..], 8)
+    } else {
+        s.parse()
+    };
+    res.map_err(|_| Error::Unexpected(s.to_string()))
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token, Error> {
+        self.tokens.next().ok_or(Error::Eof)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            t => Err(Error::Unexpected(format!("{t:?}, expected '{c}'"))),
+        }
+    }
+
+    fn peek_punct(&mut self, c: char) -> bool {
+        self.tokens.peek() == Some(&Token::Punct(c))
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match self.next()? {
+            Token::Ident(s) => Ok(s),
+            t => Err(Error::Unexpected(format!("{t:?}, expected an identifier"))),
+        }
+    }
+
+    // `a.b.c`
+    fn dotted_ident(&mut self) -> Result<String, Error> {
+        let mut s = self.ident()?;
+        while self.peek_punct('.') {
+            self.next()?;
+            s.push('.');
+            s.push_str(&self.ident()?);
+        }
+        Ok(s)
+    }
+
+    fn int(&mut self) -> Result<i128, Error> {
+        match self.next()? {
+            Token::Int(i) => Ok(i),
+            Token::Punct('-') => Ok(-self.int()?),
+            t => Err(Error::Unexpected(format!("{t:?}, expected an integer"))),
+        }
+    }
+
+    // `{ key = value; key := type; ... }`
+    fn block(&mut self) -> Result<Attributes, Error> {
+        self.expect('{')?;
+        let mut attributes = Attributes::default();
+        while !self.peek_punct('}') {
+            let key = self.dotted_ident()?;
+            let value = match self.next()? {
+                Token::TypeAssign => Value::Type(self.field_type()?),
+                Token::Punct('=') => match self.next()? {
+                    Token::Str(s) => Value::Str(s),
+                    Token::Int(i) => Value::Int(i),
+                    Token::Punct('-') => Value::Int(-self.int()?),
+                    Token::Ident(mut s) => {
+                        while self.peek_punct('.') {
+                            self.next()?;
+                            s.push('.');
+                            s.push_str(&self.ident()?);
+                        }
+                        Value::Ident(s)
+                    }
+                    t => return Err(Error::Unexpected(format!("{t:?} as value of {key}"))),
+                },
+                t => return Err(Error::Unexpected(format!("{t:?} after {key}"))),
+            };
+            self.expect(';')?;
+            attributes.0.push((key, value));
+        }
+        self.expect('}')?;
+        Ok(attributes)
+    }
+
+    fn field_type(&mut self) -> Result<FieldType, Error> {
+        let kind = self.ident()?;
+        match kind.as_str() {
+            "integer" => Ok(FieldType::Integer(self.block()?)),
+            "floating_point" => Ok(FieldType::FloatingPoint(self.block()?)),
+            "string" => {
+                let attributes = if self.peek_punct('{') {
+                    self.block()?
+                } else {
+                    Attributes::default()
+                };
+                Ok(FieldType::String(attributes))
+            }
+            "enum" => {
+                self.expect(':')?;
+                let container = Box::new(self.field_type()?);
+                Ok(FieldType::Enum {
+                    container,
+                    mappings: self.enum_mappings()?,
+                })
+            }
+            "struct" => {
+                let fields = self.fields()?;
+                let mut align = None;
+                if self.tokens.peek() == Some(&Token::Ident("align".to_string())) {
+                    self.next()?;
+                    self.expect('(')?;
+                    align = Some(self.int()? as u64);
+                    self.expect(')')?;
+                }
+                Ok(FieldType::Struct { fields, align })
+            }
+            "variant" => {
+                let mut tag = None;
+                if self.peek_punct('<') {
+                    self.next()?;
+                    tag = Some(self.dotted_ident()?);
+                    self.expect('>')?;
+                }
+                Ok(FieldType::Variant {
+                    tag,
+                    fields: self.fields()?,
+                })
+            }
+            _ => Err(Error::Unsupported(format!("type {kind}"))),
+        }
+    }
+
+    // `{ "A" = 0, "B" = 1 ... 3, C, }`
+    fn enum_mappings(&mut self) -> Result<Vec<EnumMapping>, Error> {
+        self.expect('{')?;
+        let mut mappings = Vec::new();
+        let mut next_value = 0;
+        while !self.peek_punct('}') {
+            let label = match self.next()? {
+                Token::Str(s) | Token::Ident(s) => s,
+                t => return Err(Error::Unexpected(format!("{t:?} as enum label"))),
+            };
+            let (mut lower, mut upper) = (next_value, next_value);
+            if self.peek_punct('=') {
+                self.next()?;
+                lower = self.int()?;
+                upper = lower;
+                if self.tokens.peek() == Some(&Token::Ellipsis) {
+                    self.next()?;
+                    upper = self.int()?;
+                }
+            }
+            next_value = upper + 1;
+            mappings.push(EnumMapping {
+                label,
+                lower,
+                upper,
+            });
+            if self.peek_punct(',') {
+                self.next()?;
+            }
+        }
+        self.expect('}')?;
+        Ok(mappings)
+    }
+
+    // `{ type name[dims]; ... }`
+    fn fields(&mut self) -> Result<Vec<Field>, Error> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        while !self.peek_punct('}') {
+            let field_type = self.field_type()?;
+            let name = self.ident()?;
+            let mut dims = Vec::new();
+            while self.peek_punct('[') {
+                self.next()?;
+                dims.push(match self.next()? {
+                    Token::Int(i) => Length::Static(i as u64),
+                    Token::Ident(s) => Length::Dynamic(s),
+                    t => return Err(Error::Unexpected(format!("{t:?} as length of {name}"))),
+                });
+                self.expect(']')?;
+            }
+            self.expect(';')?;
+            fields.push(Field {
+                name,
+                field_type,
+                dims,
+            });
+        }
+        self.expect('}')?;
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("testdata/ctf_fs.tsdl");
+
+    fn payload(metadata: &Metadata, event: &str) -> Vec<Field> {
+        let event = metadata
+            .events
+            .iter()
+            .find(|e| e.str("name") == Some(event))
+            .unwrap();
+        match event.field_type("fields") {
+            Some(FieldType::Struct { fields, .. }) => fields.clone(),
+            t => panic!("payload of {event:?} is {t:?}"),
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        let metadata = Metadata::parse(SAMPLE).unwrap();
+        assert_eq!(metadata.trace.int("major"), Some(1));
+        assert_eq!(metadata.trace.str("byte_order"), Some("le"));
+        assert_eq!(metadata.env.str("domain"), Some("kernel"));
+        assert_eq!(metadata.env.int("tracer_major"), Some(2));
+        assert_eq!(metadata.clocks.len(), 1);
+        assert_eq!(metadata.clocks[0].str("name"), Some("monotonic"));
+        assert_eq!(metadata.clocks[0].int("freq"), Some(2_000_000_000));
+        assert_eq!(
+            metadata.clocks[0].get("absolute").unwrap().as_bool(),
+            Some(false)
+        );
+        assert_eq!(metadata.streams.len(), 1);
+        assert_eq!(metadata.events.len(), 3);
+
+        let Some(FieldType::Struct { fields, align }) = metadata.trace.field_type("packet.header")
+        else {
+            panic!("no packet header");
+        };
+        assert_eq!(*align, Some(8));
+        assert_eq!(fields[1].name, "uuid");
+        assert_eq!(fields[1].dims, vec![Length::Static(16)]);
+
+        let Some(FieldType::Struct { fields, .. }) = metadata.streams[0].field_type("event.header")
+        else {
+            panic!("no event header");
+        };
+        let FieldType::Integer(timestamp) = &fields[1].field_type else {
+            panic!("timestamp is {:?}", fields[1].field_type);
+        };
+        assert_eq!(timestamp.str("map"), Some("clock.monotonic.value"));
+    }
+
+    #[test]
+    fn fields() {
+        let metadata = Metadata::parse(SAMPLE).unwrap();
+
+        let sched_switch = payload(&metadata, "sched_switch");
+        let FieldType::Enum {
+            container,
+            mappings,
+        } = &sched_switch[2].field_type
+        else {
+            panic!("prev_state is {:?}", sched_switch[2].field_type);
+        };
+        assert!(matches!(container.as_ref(), FieldType::Integer(a) if a.int("size") == Some(8)));
+        assert_eq!(
+            mappings[2],
+            EnumMapping {
+                label: "TASK_DEAD".to_string(),
+                lower: 64,
+                upper: 127,
+            }
+        );
+        assert!(matches!(&sched_switch[3].field_type, FieldType::String(a)
+            if a.str("encoding") == Some("UTF8")));
+
+        let ipc = payload(&metadata, "IPC");
+        assert_eq!(ipc[3].name, "_dword");
+        assert_eq!(
+            ipc[3].dims,
+            vec![Length::Dynamic("__dword_len".to_string())]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Metadata::parse("trace { major = 1; }").is_err());
+        assert!(Metadata::parse("callsite { line = 1; };").is_err());
+        assert!(Metadata::parse("trace { major = 1; /* unterminated").is_err());
+    }
+}
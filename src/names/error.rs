@@ -0,0 +1,12 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("IO error on reading the names file")]
+    Io(#[from] io::Error),
+    #[error("Invalid JSON names file")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid entry in line {0}: {1}")]
+    Line(usize, String),
+}
@@ -0,0 +1,106 @@
+use super::ObjectEntry;
+use super::error::Error;
+use crate::converter::kernel_object::{KernelObject, KernelObjectMap};
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TableFormat {
+    Json,
+    Csv,
+}
+
+impl TableFormat {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            TableFormat::Json => "kernel_objects.json",
+            TableFormat::Csv => "kernel_objects.csv",
+        }
+    }
+}
+
+/// Everything the converter learned about the kernel objects, ordered by debug id
+pub fn object_table(map: &KernelObjectMap) -> Vec<ObjectEntry> {
+    let mut entries: Vec<ObjectEntry> = map
+        .iter()
+        .map(|(addr, obj)| {
+            let base = obj.base();
+            let mut entry = ObjectEntry {
+                id: base.id.map(|id| id.0),
+                addr: Some(addr.as_u64()),
+                kobj_type: Some(obj.kind().to_string()),
+                name: base.name.clone(),
+                created: base.created,
+                destroyed: base.destroyed,
+                generation: base.generation,
+                ..Default::default()
+            };
+
+            match obj {
+                KernelObject::Thread(t) => {
+                    entry.task = t
+                        .task
+                        .and_then(|t| map.get(&t))
+                        .and_then(|t| t.id())
+                        .map(|id| id.0);
+                    entry.prio = Some(t.prio);
+                    entry.state = Some(t.state.as_str().to_string());
+                }
+                KernelObject::Gate(g) => {
+                    entry.thread = map.get(&g.thread).and_then(|t| t.id()).map(|id| id.0);
+                }
+                KernelObject::Generic(_) | KernelObject::Task(_) => (),
+            }
+            entry
+        })
+        .collect();
+
+    entries.sort_by_key(|e| (e.id, e.addr));
+    entries
+}
+
+/// Writes the object table to `path` in the given format
+pub fn write(path: &Path, format: TableFormat, entries: &[ObjectEntry]) -> Result<(), Error> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    match format {
+        TableFormat::Json => serde_json::to_writer_pretty(&mut out, entries)?,
+        TableFormat::Csv => {
+            writeln!(
+                out,
+                "id,addr,type,name,task,thread,prio,state,created,destroyed,generation"
+            )?;
+            for e in entries {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{},{},{}",
+                    csv_field(e.id),
+                    csv_field(e.addr.map(|a| format!("{a:#x}"))),
+                    csv_field(e.kobj_type.as_deref()),
+                    csv_quote(&e.name),
+                    csv_field(e.task),
+                    csv_field(e.thread),
+                    csv_field(e.prio),
+                    csv_field(e.state.as_deref()),
+                    csv_field(e.created),
+                    csv_field(e.destroyed),
+                    e.generation,
+                )?;
+            }
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+fn csv_field<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+// object names are arbitrary bytes from the kernel, so always quote them
+fn csv_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
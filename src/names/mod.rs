@@ -0,0 +1,218 @@
+//! Loading of object names known before the conversion starts (`--names <file>`).
+//!
+//! Three formats are supported:
+//!
+//! * JSON, an array of objects with the optional fields `id` (debug id), `addr` (kernel address),
+//!   `type`, `name` and `task` (debug id of the task of a thread). Each entry needs an `id` or an
+//!   `addr`.
+//! * A text format with one object per line and `#` comments:
+//!   `<key> <type> [task=<id>] <name>`, where the key is a decimal debug id, a `0x` prefixed
+//!   kernel address or both as `<id>@0x<addr>`, and the type is `-` if unknown. The name is the
+//!   rest of the line and may be empty.
+//! * The kernel object list of JDB, lines of the form `<hex id> [<Type>] <hex address> ...` with
+//!   the name of the object in curly braces, e.g. `1a [Thread  ] ffff80000123c000 ... {sigma0}`.
+//!   Lines which don't look like this (headers, prompts) are skipped.
+//!
+//! The format is detected from the content: JSON if the file starts with `[`, JDB if any line
+//! looks like a JDB object list line, the text format otherwise.
+//!
+//! The object table written at the end of a conversion (`--object-table json`) uses the JSON
+//! format, so it can be passed to `--names` of a later run. Its idle threads and destroyed objects
+//! are skipped then: the idle threads are set up by the converter itself and the destroyed objects
+//! don't exist at the start of the next trace.
+
+pub mod error;
+pub mod export;
+
+use crate::converter::kernel_object::{DbgId, KobjAddr, KobjType, ObjectPreset, ThreadState};
+use error::Error;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// An object of the JSON format. Only the fields up to `task` are used when loading, the others
+/// are written to the object table for post-processing.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ObjectEntry {
+    pub id: Option<u64>,
+    pub addr: Option<u64>,
+    #[serde(rename = "type")]
+    pub kobj_type: Option<String>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<u64>,
+    /// Debug id of the thread an IPC gate is bound to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prio: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destroyed: Option<u64>,
+    #[serde(default)]
+    pub generation: u32,
+}
+
+/// Reads the names file at `path`
+pub fn load(path: &Path) -> Result<Vec<ObjectPreset>, Error> {
+    let content = fs::read_to_string(path)?;
+    let jdb_line = jdb_line_regex();
+
+    if content.trim_start().starts_with('[') {
+        parse_json(&content)
+    } else if content.lines().any(|l| jdb_line.is_match(l)) {
+        Ok(parse_jdb(&content, &jdb_line))
+    } else {
+        parse_text(&content)
+    }
+}
+
+// gate objects are addressed exactly, everything else by the page the object lives in (see the
+// handling of NAM events)
+fn object_addr(addr: u64, kobj_type: Option<KobjType>) -> KobjAddr {
+    if kobj_type == Some(KobjType::Gate) {
+        KobjAddr::exact(addr)
+    } else {
+        KobjAddr::masked(addr)
+    }
+}
+
+fn parse_json(content: &str) -> Result<Vec<ObjectPreset>, Error> {
+    let entries: Vec<ObjectEntry> = serde_json::from_str(content)?;
+
+    entries
+        .into_iter()
+        .enumerate()
+        .filter(|(_, e)| !is_stale(e))
+        .map(|(i, e)| {
+            if e.id.is_none() && e.addr.is_none() {
+                return Err(Error::Line(i + 1, "entry without id and addr".to_string()));
+            }
+            let kobj_type = e
+                .kobj_type
+                .map(|t| t.parse::<KobjType>())
+                .transpose()
+                .map_err(|err| Error::Line(i + 1, err))?;
+
+            Ok(ObjectPreset {
+                id: e.id.map(DbgId),
+                addr: e.addr.map(|a| object_addr(a, kobj_type)),
+                name: e.name,
+                kobj_type,
+                task: e.task.map(DbgId),
+            })
+        })
+        .collect()
+}
+
+// idle threads and destroyed objects of an object table
+fn is_stale(entry: &ObjectEntry) -> bool {
+    let stale_states = [ThreadState::Idle.as_str(), ThreadState::Dead.as_str()];
+    entry.destroyed.is_some()
+        || entry
+            .state
+            .as_deref()
+            .is_some_and(|s| stale_states.contains(&s))
+}
+
+fn parse_text(content: &str) -> Result<Vec<ObjectPreset>, Error> {
+    let mut presets = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line_nr = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, rest) = split_field(line);
+        let (kobj_type, mut rest) = split_field(rest);
+        let kobj_type = match kobj_type {
+            "-" => None,
+            "" => return Err(Error::Line(line_nr, "missing object type".to_string())),
+            t => Some(t.parse::<KobjType>().map_err(|e| Error::Line(line_nr, e))?),
+        };
+
+        let mut task = None;
+        if let Some(t) = rest.strip_prefix("task=") {
+            let (id, name) = split_field(t);
+            task = Some(DbgId(parse_dec(id, line_nr)?));
+            rest = name;
+        }
+
+        let (id, addr) = match key.split_once('@') {
+            Some((id, addr)) => (Some(id), Some(addr)),
+            None if key.starts_with("0x") => (None, Some(key)),
+            None => (Some(key), None),
+        };
+        let id = id.map(|id| parse_dec(id, line_nr)).transpose()?;
+        let addr = addr.map(|a| parse_hex(a, line_nr)).transpose()?;
+
+        presets.push(ObjectPreset {
+            id: id.map(DbgId),
+            addr: addr.map(|a| object_addr(a, kobj_type)),
+            name: rest.to_string(),
+            kobj_type,
+            task,
+        });
+    }
+
+    Ok(presets)
+}
+
+fn jdb_line_regex() -> Regex {
+    Regex::new(
+        r"^\s*([0-9a-fA-F]+)\s+\[\s*([A-Za-z_]+)[^\]]*\]\s+(?:0x)?([0-9a-fA-F]{8,16})\b(.*)$",
+    )
+    .unwrap()
+}
+
+fn parse_jdb(content: &str, jdb_line: &Regex) -> Vec<ObjectPreset> {
+    let name_re = Regex::new(r"\{([^}]*)\}").unwrap();
+
+    content
+        .lines()
+        .filter_map(|line| jdb_line.captures(line))
+        .filter_map(|cap| {
+            let id = u64::from_str_radix(&cap[1], 16).ok()?;
+            let addr = u64::from_str_radix(&cap[3], 16).ok()?;
+            // JDB knows more object types than the converter, those are kept as generic objects
+            let kobj_type = cap[2].parse::<KobjType>().ok();
+            let name = name_re
+                .captures(&cap[4])
+                .map(|n| n[1].trim().to_string())
+                .unwrap_or_default();
+
+            Some(ObjectPreset {
+                id: Some(DbgId(id)),
+                addr: Some(object_addr(addr, kobj_type)),
+                name,
+                kobj_type,
+                task: None,
+            })
+        })
+        .collect()
+}
+
+// splits off the first whitespace separated field
+fn split_field(s: &str) -> (&str, &str) {
+    match s.split_once(char::is_whitespace) {
+        Some((field, rest)) => (field, rest.trim_start()),
+        None => (s, ""),
+    }
+}
+
+fn parse_dec(s: &str, line_nr: usize) -> Result<u64, Error> {
+    s.parse::<u64>()
+        .map_err(|e| Error::Line(line_nr, format!("invalid debug id '{s}' ({e})")))
+}
+
+fn parse_hex(s: &str, line_nr: usize) -> Result<u64, Error> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|e| Error::Line(line_nr, format!("invalid address '{s}' ({e})")))
+}
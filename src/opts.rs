@@ -1,3 +1,11 @@
+use crate::analysis::ipc_graph::GraphNodes;
+use crate::analysis::sched_stats::StatsFormat;
+use crate::filter::Condition;
+use crate::metadata::CtfVersion;
+use crate::names::export::TableFormat;
+use crate::sampling::{SampleRule, parse_rate_limit, parse_sample};
+use crate::sink::dump::DumpFormat;
+use crate::symbols::parse_task_symbols;
 use babeltrace2_sys::LoggingLevel;
 use clap::Parser;
 use std::path::PathBuf;
@@ -26,4 +34,87 @@ pub struct Opts {
     /// Output directory to write traces to
     #[clap(short = 'o', long, default_value = "ctf_trace")]
     pub output: PathBuf,
+
+    /// Don't write the CTF trace, only the outputs of the other options (e.g. `--format`), without
+    /// babeltrace
+    #[clap(long)]
+    pub no_ctf: bool,
+
+    /// CTF version of the written trace
+    #[clap(long, value_enum, default_value = "1.8")]
+    pub ctf_version: CtfVersion,
+
+    /// File with names, types and tasks of kernel objects to know before the first NAM event
+    /// (JSON, text or a JDB object list, see `src/names/mod.rs`)
+    #[clap(long)]
+    pub names: Option<PathBuf>,
+
+    /// Only write the events matching this condition, `<key>=<value>[,<value>...]` with the key
+    /// type, cpu, thread, task or time (can be given multiple times, see `src/filter/mod.rs`)
+    #[clap(long)]
+    pub include: Vec<Condition>,
+
+    /// Leave out the events matching this condition, as `--include`
+    #[clap(long)]
+    pub exclude: Vec<Condition>,
+
+    /// Only convert 1 in n events of a type on each CPU, as <type>=<n> (can be given multiple
+    /// times, see `src/sampling/mod.rs`)
+    #[clap(long, value_parser = parse_sample)]
+    pub sample: Vec<SampleRule>,
+
+    /// Convert at most this many events of a type per second on each CPU, as
+    /// <type>=<events per second>[:<burst>] (can be given multiple times)
+    #[clap(long, value_parser = parse_rate_limit)]
+    pub rate_limit: Vec<SampleRule>,
+
+    /// Write the final kernel object table next to the trace (kernel_objects.json/csv)
+    #[clap(long, value_enum)]
+    pub object_table: Option<TableFormat>,
+
+    /// Fiasco kernel ELF, to resolve kernel instruction pointers to function+offset
+    #[clap(long)]
+    pub symbols: Option<PathBuf>,
+
+    /// ELF of a task as <task name>=<path>, to resolve user instruction pointers of its threads
+    /// (can be given multiple times)
+    #[clap(long, value_parser = parse_task_symbols)]
+    pub task_symbols: Vec<(String, PathBuf)>,
+
+    /// Write the IPC communication graph next to the trace (ipc_graph.dot and ipc_graph.json)
+    #[clap(long)]
+    pub ipc_graph: bool,
+
+    /// Whether the callers in the IPC graph are threads or tasks
+    #[clap(long, value_enum, default_value = "thread")]
+    pub ipc_graph_nodes: GraphNodes,
+
+    /// Report CPU time, context switches and idle time per thread, task and CPU, and the budgets
+    /// of the scheduling contexts
+    #[clap(long, value_enum)]
+    pub sched_stats: Option<StatsFormat>,
+
+    /// Also write the trace in the Perfetto format to this file, for ui.perfetto.dev
+    #[cfg(feature = "perfetto")]
+    #[clap(long)]
+    pub perfetto: Option<PathBuf>,
+
+    /// Also print every decoded event with its common fields and resolved thread, one per line
+    #[clap(long, value_enum)]
+    pub format: Option<DumpFormat>,
+
+    /// File to write the events of `--format` to, instead of stdout
+    #[clap(long, requires = "format")]
+    pub format_output: Option<PathBuf>,
+
+    /// Write one Parquet table per event type and the kernel object table to this directory
+    #[cfg(feature = "parquet")]
+    #[clap(long)]
+    pub parquet: Option<PathBuf>,
+
+    /// Write the events, kernel objects, thread states and IPC pairs to this SQLite database
+    /// (see `src/database/schema.sql`)
+    #[cfg(feature = "sqlite")]
+    #[clap(long)]
+    pub sqlite: Option<PathBuf>,
 }
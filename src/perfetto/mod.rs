@@ -0,0 +1,359 @@
+//! Perfetto trace output (`--perfetto <file>`), for viewing traces in ui.perfetto.dev.
+//!
+//! [`PerfettoTrace`] is a [`TraceSink`], so threads and tasks carry the same names as in the CTF
+//! trace. The trace has
+//!
+//! * a track per CPU with a slice for every thread running on it,
+//! * a track per thread (grouped by task) with a `running` slice for each time it ran,
+//! * instant events for IRQs (on the CPU track) and page faults (on the thread track),
+//! * flows from the sending to the receiving thread of each IPC.
+//!
+//! The idle threads only show up as gaps on the CPU tracks. Threads whose debug id isn't known
+//! have their slices only on the CPU tracks, their other events are on a shared `unknown` thread
+//! track.
+
+pub mod proto;
+
+use crate::analysis::ticks_to_ns;
+use crate::converter::kernel_object::UNKNOWN_TID;
+use crate::event::Event;
+use crate::sink::error::Error;
+use crate::sink::{Detail, SinkEvent, ThreadInfo, TraceSink};
+use proto::{
+    DebugAnnotation, ProcessDescriptor, ThreadDescriptor, TracePacket, TrackDescriptor, TrackEvent,
+};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// all packets are written by one writer
+const SEQUENCE_ID: u32 = 1;
+// the idle threads of all CPUs get debug id 0
+const IDLE_TID: i64 = 0;
+// the threads whose debug id isn't known share one thread track
+const UNKNOWN_NAME: &str = "unknown";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum TrackKey {
+    Cpu(u8),
+    Process(i64),
+    Thread(i64),
+}
+
+pub struct PerfettoTrace {
+    out: BufWriter<File>,
+    buf: Vec<u8>,
+    clock_frequency: u64,
+    // uuids of the tracks described so far, with the name they were described with
+    tracks: HashMap<TrackKey, (u64, String)>,
+    // thread with an open slice per CPU
+    running: HashMap<u8, i64>,
+    last_tsc: u64,
+    // the first write error, later writes are skipped
+    error: Option<io::Error>,
+    first_packet: bool,
+}
+
+impl PerfettoTrace {
+    pub fn create(path: &Path, clock_frequency: u64) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            buf: Vec::new(),
+            clock_frequency,
+            tracks: HashMap::new(),
+            running: HashMap::new(),
+            last_tsc: 0,
+            error: None,
+            first_packet: true,
+        })
+    }
+
+    /// Ends the slices of `prev` and begins the ones of `next` on the CPU and thread tracks
+    pub fn context_switch(&mut self, cpu: u8, tsc: u64, prev: &ThreadInfo, next: &ThreadInfo) {
+        if let Some(tid) = self.running.remove(&cpu) {
+            let cpu_track = self.cpu_track(cpu);
+            self.slice_end(tsc, cpu_track);
+            // unknown threads have no running slice on their shared track
+            if tid != UNKNOWN_TID
+                && let Some((thread_track, _)) = self.tracks.get(&TrackKey::Thread(tid))
+            {
+                self.slice_end(tsc, *thread_track);
+            }
+        }
+        // the previous thread may have been renamed while it ran
+        if prev.tid != IDLE_TID && prev.tid != UNKNOWN_TID {
+            self.thread_track(prev);
+        }
+
+        if next.tid != IDLE_TID {
+            let cpu_track = self.cpu_track(cpu);
+            self.slice_begin(
+                tsc,
+                cpu_track,
+                &next.name,
+                vec![tid_annotation("tid", next.tid)],
+            );
+            if next.tid != UNKNOWN_TID {
+                let thread_track = self.thread_track(next);
+                self.slice_begin(tsc, thread_track, "running", vec![annotation("cpu", cpu)]);
+            }
+            self.running.insert(cpu, next.tid);
+        }
+    }
+
+    /// An IRQ on `cpu`
+    pub fn irq(&mut self, cpu: u8, tsc: u64, irq_obj: u64, pin: u64) {
+        let track = self.cpu_track(cpu);
+        self.emit(
+            tsc,
+            TrackEvent {
+                r#type: Some(proto::TYPE_INSTANT),
+                track_uuid: Some(track),
+                name: Some("irq".to_string()),
+                debug_annotations: vec![annotation("obj", irq_obj), annotation("pin", pin)],
+                ..Default::default()
+            },
+        );
+    }
+
+    /// A page fault of `thread`
+    pub fn page_fault(&mut self, thread: &ThreadInfo, tsc: u64, pfa: u64, error: u64) {
+        let track = self.thread_track(thread);
+        self.emit(
+            tsc,
+            TrackEvent {
+                r#type: Some(proto::TYPE_INSTANT),
+                track_uuid: Some(track),
+                name: Some("page fault".to_string()),
+                debug_annotations: vec![annotation("pfa", pfa), annotation("error", error)],
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Start of the IPC flow `flow_id` from `sender` to `receiver`
+    pub fn ipc_send(&mut self, sender: &ThreadInfo, receiver: &ThreadInfo, tsc: u64, flow_id: u64) {
+        let track = self.thread_track(sender);
+        self.emit(
+            tsc,
+            TrackEvent {
+                r#type: Some(proto::TYPE_INSTANT),
+                track_uuid: Some(track),
+                name: Some("ipc send".to_string()),
+                debug_annotations: vec![tid_annotation("dst_tid", receiver.tid)],
+                flow_ids: vec![flow_id],
+                ..Default::default()
+            },
+        );
+    }
+
+    /// End of the IPC flow `flow_id` from `sender` on `receiver`
+    pub fn ipc_receive(
+        &mut self,
+        receiver: &ThreadInfo,
+        sender: &ThreadInfo,
+        tsc: u64,
+        flow_id: u64,
+    ) {
+        let track = self.thread_track(receiver);
+        self.emit(
+            tsc,
+            TrackEvent {
+                r#type: Some(proto::TYPE_INSTANT),
+                track_uuid: Some(track),
+                name: Some("ipc receive".to_string()),
+                debug_annotations: vec![tid_annotation("src_tid", sender.tid)],
+                terminating_flow_ids: vec![flow_id],
+                ..Default::default()
+            },
+        );
+    }
+
+    fn cpu_track(&mut self, cpu: u8) -> u64 {
+        self.track(TrackKey::Cpu(cpu), format!("CPU {cpu}"), |uuid, name| {
+            TrackDescriptor {
+                uuid: Some(uuid),
+                name: Some(name.to_string()),
+                ..Default::default()
+            }
+        })
+    }
+
+    fn thread_track(&mut self, thread: &ThreadInfo) -> u64 {
+        let (task, name) = if thread.tid == UNKNOWN_TID {
+            (UNKNOWN_NAME.to_string(), UNKNOWN_NAME.to_string())
+        } else {
+            (thread.task.clone(), thread.name.clone())
+        };
+        let pid = thread.pid;
+        self.track(TrackKey::Process(pid), task, |uuid, name| TrackDescriptor {
+            uuid: Some(uuid),
+            process: Some(ProcessDescriptor {
+                pid: Some(pid as i32),
+                process_name: Some(name.to_string()),
+            }),
+            ..Default::default()
+        });
+
+        let tid = thread.tid;
+        self.track(TrackKey::Thread(tid), name, |uuid, name| TrackDescriptor {
+            uuid: Some(uuid),
+            thread: Some(ThreadDescriptor {
+                pid: Some(pid as i32),
+                tid: Some(tid as i32),
+                thread_name: Some(name.to_string()),
+            }),
+            ..Default::default()
+        })
+    }
+
+    // the uuid of the track for `key`, (re-)describing it if it is new or got renamed
+    fn track(
+        &mut self,
+        key: TrackKey,
+        name: String,
+        descriptor: impl FnOnce(u64, &str) -> TrackDescriptor,
+    ) -> u64 {
+        let next_uuid = self.tracks.len() as u64 + 1;
+        let (uuid, known_name) = self
+            .tracks
+            .entry(key)
+            .or_insert_with(|| (next_uuid, String::new()));
+        let uuid = *uuid;
+        if uuid != next_uuid && *known_name == name {
+            return uuid;
+        }
+
+        *known_name = name;
+        let packet = TracePacket {
+            track_descriptor: Some(descriptor(uuid, known_name)),
+            ..Default::default()
+        };
+        self.write_packet(packet);
+        uuid
+    }
+
+    fn slice_begin(&mut self, tsc: u64, track: u64, name: &str, args: Vec<DebugAnnotation>) {
+        self.emit(
+            tsc,
+            TrackEvent {
+                r#type: Some(proto::TYPE_SLICE_BEGIN),
+                track_uuid: Some(track),
+                name: Some(name.to_string()),
+                debug_annotations: args,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn slice_end(&mut self, tsc: u64, track: u64) {
+        self.emit(
+            tsc,
+            TrackEvent {
+                r#type: Some(proto::TYPE_SLICE_END),
+                track_uuid: Some(track),
+                ..Default::default()
+            },
+        );
+    }
+
+    fn emit(&mut self, tsc: u64, event: TrackEvent) {
+        self.last_tsc = self.last_tsc.max(tsc);
+        self.write_packet(TracePacket {
+            timestamp: Some(ticks_to_ns(tsc, self.clock_frequency)),
+            track_event: Some(event),
+            ..Default::default()
+        });
+    }
+
+    // appends the packet to the file as an entry of `Trace.packet` (field 1)
+    fn write_packet(&mut self, mut packet: TracePacket) {
+        if self.error.is_some() {
+            return;
+        }
+
+        packet.trusted_packet_sequence_id = Some(SEQUENCE_ID);
+        if self.first_packet {
+            packet.sequence_flags = Some(proto::SEQ_INCREMENTAL_STATE_CLEARED);
+            self.first_packet = false;
+        }
+
+        self.buf.clear();
+        prost::encoding::message::encode(1, &packet, &mut self.buf);
+        if let Err(e) = self.out.write_all(&self.buf) {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl TraceSink for PerfettoTrace {
+    fn event(&mut self, event: &SinkEvent) {
+        match event.event {
+            Event::Irq(ev) => self.irq(event.cpu, event.tsc, ev.obj, ev.pin),
+            Event::Pf(ev) => self.page_fault(&event.thread, event.tsc, ev.pfa, ev.error),
+            _ => (),
+        }
+
+        for detail in &event.details {
+            match detail {
+                Detail::ContextSwitch { prev, next, .. } => {
+                    self.context_switch(event.cpu, event.tsc, prev, next)
+                }
+                Detail::IpcSend { flow_id, receiver } => {
+                    self.ipc_send(&event.thread, receiver, event.tsc, *flow_id)
+                }
+                Detail::IpcReceive { flow_id, sender } => {
+                    self.ipc_receive(&event.thread, sender, event.tsc, *flow_id)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // the slices cover all context switches, whether converted or not
+    fn left_out(&mut self, event: &SinkEvent) {
+        for detail in &event.details {
+            if let Detail::ContextSwitch { prev, next, .. } = detail {
+                self.context_switch(event.cpu, event.tsc, prev, next);
+            }
+        }
+    }
+
+    /// Ends the slices still open at the last event and flushes the file
+    fn finish(&mut self) -> Result<(), Error> {
+        let running: Vec<(u8, i64)> = self.running.drain().collect();
+        for (cpu, tid) in running {
+            let cpu_track = self.cpu_track(cpu);
+            self.slice_end(self.last_tsc, cpu_track);
+            // unknown threads have no running slice on their shared track
+            if tid != UNKNOWN_TID
+                && let Some((thread_track, _)) = self.tracks.get(&TrackKey::Thread(tid))
+            {
+                self.slice_end(self.last_tsc, *thread_track);
+            }
+        }
+
+        if let Some(e) = self.error.take() {
+            return Err(e.into());
+        }
+        Ok(self.out.flush()?)
+    }
+}
+
+fn annotation(name: &str, value: impl TryInto<u64>) -> DebugAnnotation {
+    DebugAnnotation {
+        name: Some(name.to_string()),
+        uint_value: Some(value.try_into().unwrap_or(0)),
+        ..Default::default()
+    }
+}
+
+// thread ids are signed, as unknown threads have `UNKNOWN_TID`
+fn tid_annotation(name: &str, tid: i64) -> DebugAnnotation {
+    DebugAnnotation {
+        name: Some(name.to_string()),
+        int_value: Some(tid),
+        ..Default::default()
+    }
+}
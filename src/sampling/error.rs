@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("expected <type>=<n>, got '{0}'")]
+    Sample(String),
+    #[error("expected <type>=<events per second>[:<burst>], got '{0}'")]
+    RateLimit(String),
+}
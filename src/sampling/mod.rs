@@ -0,0 +1,164 @@
+//! Thinning out of high-frequency event types in the output.
+//!
+//! With IPC and page fault tracing both on, most of the conversion time goes into writing these
+//! events to CTF and the sinks. The [`Sampler`] discards events of the configured types on every
+//! CPU independently:
+//!
+//! - `--sample <type>=<n>` keeps 1 in n events
+//! - `--rate-limit <type>=<events per second>[:<burst>]` keeps them at that rate, with a token
+//!   bucket filled by the event timestamps and holding up to `burst` events (the rate by default)
+//!
+//! The types are named as for `--include` (see [`crate::filter`]). The sampler runs in the
+//! [`Enricher`](crate::enrich::Enricher) on the events kept by the filter, after their kernel
+//! objects are tracked, so discarding `NAM`, `FACTORY` or `CONTEXTSWITCH` events doesn't lose
+//! names, objects or thread states. Discarded events are left out of the IPC analyses, the CTF
+//! trace and the sinks, except for the context switches handed to
+//! [`TraceSink::left_out`](crate::sink::TraceSink::left_out). The number of discarded events of each CPU is written to its CTF stream as
+//! discarded events messages, and reported per type in the summary.
+
+pub mod error;
+
+use crate::event::Event;
+use crate::filter::is_type;
+use error::Error;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    /// Keep 1 in n events
+    Every(u64),
+    /// Token bucket
+    Rate { per_second: f64, burst: f64 },
+}
+
+/// The limit of an event type, given by `--sample` or `--rate-limit`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleRule {
+    /// Upper case name of the event or its tbuf type
+    pub event_type: String,
+    pub limit: Limit,
+}
+
+/// Parses `<type>=<n>` of `--sample`
+pub fn parse_sample(arg: &str) -> Result<SampleRule, Error> {
+    let err = || Error::Sample(arg.to_string());
+    let (event_type, n) = arg.split_once('=').ok_or_else(err)?;
+    let n = n.parse::<u64>().map_err(|_| err())?;
+    if event_type.is_empty() || n == 0 {
+        return Err(err());
+    }
+    Ok(SampleRule {
+        event_type: event_type.to_uppercase(),
+        limit: Limit::Every(n),
+    })
+}
+
+/// Parses `<type>=<events per second>[:<burst>]` of `--rate-limit`
+pub fn parse_rate_limit(arg: &str) -> Result<SampleRule, Error> {
+    let err = || Error::RateLimit(arg.to_string());
+    let (event_type, limit) = arg.split_once('=').ok_or_else(err)?;
+    let (rate, burst) = match limit.split_once(':') {
+        Some((rate, burst)) => (rate, Some(burst)),
+        None => (limit, None),
+    };
+    let per_second = rate.parse::<f64>().map_err(|_| err())?;
+    let burst = match burst {
+        Some(b) => b.parse::<f64>().map_err(|_| err())?,
+        None => per_second.max(1.0),
+    };
+    if event_type.is_empty() || !per_second.is_finite() || per_second <= 0.0 || burst < 1.0 {
+        return Err(err());
+    }
+    Ok(SampleRule {
+        event_type: event_type.to_uppercase(),
+        limit: Limit::Rate { per_second, burst },
+    })
+}
+
+// token bucket of a rate limited type on a CPU
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_tsc: u64,
+}
+
+#[derive(Debug)]
+pub struct Sampler {
+    rules: Vec<SampleRule>,
+    clock_frequency: u64,
+    // state of the rules per rule index and CPU
+    counts: HashMap<(usize, u8), u64>,
+    buckets: HashMap<(usize, u8), Bucket>,
+    // discarded since the last kept event of the CPU
+    pending: HashMap<u8, u64>,
+    discarded: BTreeMap<String, u64>,
+}
+
+impl Sampler {
+    pub fn new(rules: Vec<SampleRule>, clock_frequency: u64) -> Self {
+        Self {
+            rules,
+            clock_frequency,
+            counts: HashMap::new(),
+            buckets: HashMap::new(),
+            pending: HashMap::new(),
+            discarded: BTreeMap::new(),
+        }
+    }
+
+    /// Whether `event` is converted. Counts the events which aren't.
+    pub fn keep(&mut self, event: &Event) -> bool {
+        let rule = self
+            .rules
+            .iter()
+            .position(|r| is_type(event, &r.event_type));
+        let Some(rule) = rule else {
+            return true;
+        };
+        let common = event.event_common();
+        let keep = match self.rules[rule].limit {
+            Limit::Every(n) => {
+                let seen = self.counts.entry((rule, common.cpu)).or_default();
+                *seen += 1;
+                (*seen - 1).is_multiple_of(n)
+            }
+            Limit::Rate { per_second, burst } => {
+                let bucket = self.buckets.entry((rule, common.cpu)).or_insert(Bucket {
+                    tokens: burst,
+                    last_tsc: common.tsc,
+                });
+                let elapsed = common.tsc.saturating_sub(bucket.last_tsc) as f64;
+                bucket.tokens =
+                    (bucket.tokens + elapsed * per_second / self.clock_frequency as f64).min(burst);
+                bucket.last_tsc = bucket.last_tsc.max(common.tsc);
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if !keep {
+            *self.pending.entry(common.cpu).or_default() += 1;
+            *self.discarded.entry(event.to_string()).or_default() += 1;
+        }
+        keep
+    }
+
+    /// Number of events of `cpu` discarded since the last call
+    pub fn take_pending(&mut self, cpu: u8) -> u64 {
+        self.pending.remove(&cpu).unwrap_or(0)
+    }
+
+    /// The CPUs and numbers of the events discarded since the last kept one of the CPU
+    pub fn drain_pending(&mut self) -> impl Iterator<Item = (u8, u64)> + '_ {
+        self.pending.drain()
+    }
+
+    /// Number of discarded events per event name
+    pub fn discarded(&self) -> &BTreeMap<String, u64> {
+        &self.discarded
+    }
+}
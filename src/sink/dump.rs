@@ -0,0 +1,189 @@
+//! Line based dumps of the decoded events (`--format jsonl|text`), to read and grep a trace
+//! without babeltrace.
+//!
+//! Every event becomes one line with its type, the fields of [`EventCommon`], the thread it
+//! happened in (as resolved by the enricher) and its payload. The payload fields are the ones of
+//! the structs in `src/event/`, without their padding. The output only depends on the input
+//! events, so it can be used as golden output for the `EventParser`.
+
+use super::error::Error;
+use super::{SinkEvent, ThreadInfo, TraceSink};
+use crate::analysis::ticks_to_ns;
+use crate::event::common::EventCommon;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// Similar to the pretty output of `babeltrace2`
+    Text,
+}
+
+#[derive(Serialize)]
+struct JsonThread<'a> {
+    tid: i64,
+    pid: i64,
+    name: &'a str,
+    task: &'a str,
+    state: Option<&'static str>,
+}
+
+impl<'a> From<&'a ThreadInfo> for JsonThread<'a> {
+    fn from(thread: &'a ThreadInfo) -> Self {
+        Self {
+            tid: thread.tid,
+            pid: thread.pid,
+            name: &thread.name,
+            task: &thread.task,
+            state: thread.state.map(|s| s.as_str()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonEvent<'a> {
+    event: String,
+    #[serde(flatten)]
+    common: EventCommon,
+    thread: JsonThread<'a>,
+    fields: Value,
+}
+
+pub struct EventDump {
+    format: DumpFormat,
+    out: Box<dyn Write>,
+    clock_frequency: u64,
+    // timestamp (ns) of the previous event per CPU, for the deltas of the text format
+    last_ns: HashMap<u8, u64>,
+    line: String,
+    // the first write error, later writes are skipped
+    error: Option<io::Error>,
+}
+
+impl EventDump {
+    pub fn new(format: DumpFormat, out: Box<dyn Write>, clock_frequency: u64) -> Self {
+        Self {
+            format,
+            out,
+            clock_frequency,
+            last_ns: HashMap::new(),
+            line: String::new(),
+            error: None,
+        }
+    }
+
+    /// Dump to the file at `path`, or to stdout without one
+    pub fn create(
+        format: DumpFormat,
+        path: Option<&Path>,
+        clock_frequency: u64,
+    ) -> io::Result<Self> {
+        let out: Box<dyn Write> = match path {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(BufWriter::new(io::stdout())),
+        };
+        Ok(Self::new(format, out, clock_frequency))
+    }
+
+    fn jsonl(&mut self, event: &SinkEvent) {
+        let json = JsonEvent {
+            event: event.event.to_string(),
+            common: event.event.event_common(),
+            thread: (&event.thread).into(),
+            fields: event.payload(),
+        };
+        self.line = serde_json::to_string(&json).unwrap_or_default();
+        self.line.push('\n');
+    }
+
+    fn text(&mut self, event: &SinkEvent) {
+        let common = event.event.event_common();
+        let ns = ticks_to_ns(event.tsc, self.clock_frequency);
+        let delta = self
+            .last_ns
+            .insert(event.cpu, ns)
+            .map_or(0, |last| ns.saturating_sub(last));
+
+        let line = &mut self.line;
+        line.clear();
+        let _ = write!(
+            line,
+            "[{}.{:09}] (+{}.{:09}) cpu {} {}: {{ number = {}, ip = {:#x}, ctx = {:#x}, \
+             thread = {:?}, tid = {}, task = {:?} }}, {{ ",
+            ns / 1_000_000_000,
+            ns % 1_000_000_000,
+            delta / 1_000_000_000,
+            delta % 1_000_000_000,
+            event.cpu,
+            event.event,
+            common.number,
+            common.ip,
+            common.ctx,
+            event.thread.name,
+            event.thread.tid,
+            event.thread.task,
+        );
+        if let Value::Object(fields) = event.payload() {
+            for (i, (name, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    line.push_str(", ");
+                }
+                let _ = write!(line, "{name} = ");
+                write_value(line, value);
+            }
+        }
+        line.push_str(" }\n");
+    }
+}
+
+impl TraceSink for EventDump {
+    fn event(&mut self, event: &SinkEvent) {
+        if self.error.is_some() {
+            return;
+        }
+
+        match self.format {
+            DumpFormat::Jsonl => self.jsonl(event),
+            DumpFormat::Text => self.text(event),
+        }
+        if let Err(e) = self.out.write_all(self.line.as_bytes()) {
+            self.error = Some(e);
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e.into());
+        }
+        Ok(self.out.flush()?)
+    }
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::String(s) => {
+            let _ = write!(out, "{s:?}");
+        }
+        Value::Array(values) => {
+            out.push('[');
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, v);
+            }
+            out.push(']');
+        }
+        v => {
+            let _ = write!(out, "{v}");
+        }
+    }
+}
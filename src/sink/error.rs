@@ -0,0 +1,18 @@
+#[cfg(feature = "parquet")]
+use crate::columnar;
+#[cfg(feature = "sqlite")]
+use crate::database;
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("IO error on writing the output")]
+    Io(#[from] io::Error),
+    #[cfg(feature = "parquet")]
+    #[error("Could not write the Parquet tables ({0})")]
+    Columnar(#[from] columnar::error::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("Could not write the SQLite database ({0})")]
+    Database(#[from] database::error::Error),
+}
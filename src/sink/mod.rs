@@ -0,0 +1,197 @@
+//! Backend-neutral view of the conversion.
+//!
+//! The [`Enricher`](crate::enrich::Enricher) tracks the kernel objects and hands a [`SinkEvent`]
+//! for every event the filter keeps to every registered [`TraceSink`]: the raw event together with
+//! what is known at that point, i.e. the resolved thread and task names, thread states, the CPU,
+//! the symbols and the [`Detail`]s derived from it (context switches, IPC flows and results,
+//! object lifetimes, scheduling context budgets). Sinks are plain Rust, so further output formats
+//! and analyses don't need to know anything about babeltrace.
+//!
+//! The CTF writer is a sink as well, [`CtfSink`](crate::converter::CtfSink) queues the events for
+//! the babeltrace source of each CPU, as babeltrace pulls the messages from it.
+
+pub mod dump;
+pub mod error;
+
+use crate::analysis::ipc_latency::CompletedIpc;
+use crate::converter::kernel_object::{
+    DbgId, KernelObject, KernelObjectMap, KobjAddr, KobjType, ThreadState, UNKNOWN_TID,
+};
+use crate::event::Event;
+use error::Error;
+use serde_json::Value;
+
+/// A thread as the enricher knows it at the time of an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    /// Debug id of the thread, [`UNKNOWN_TID`] if it isn't known
+    pub tid: i64,
+    /// Debug id of its task (see [`KernelObjectMap::task_pid`]), or its own if the task is unknown
+    pub pid: i64,
+    pub name: String,
+    pub task: String,
+    /// `None` if the object isn't known to be a thread
+    pub state: Option<ThreadState>,
+    /// Priority, `None` if the object isn't known to be a thread
+    pub prio: Option<u64>,
+}
+
+impl ThreadInfo {
+    /// The thread at `addr`
+    pub fn resolve(map: &KernelObjectMap, addr: KobjAddr) -> Self {
+        match map.get(&addr) {
+            Some(KernelObject::Thread(t)) => {
+                let tid = t.base.tid();
+                let pid = map.task_pid(t.task, tid).unwrap_or(tid);
+                Self {
+                    tid,
+                    pid,
+                    name: map.label(addr),
+                    task: map.task_label(pid),
+                    state: Some(t.state),
+                    prio: Some(t.prio),
+                }
+            }
+            Some(o) => {
+                let tid = o.tid();
+                Self {
+                    tid,
+                    pid: tid,
+                    name: map.label(addr),
+                    task: map.task_label(tid),
+                    state: None,
+                    prio: None,
+                }
+            }
+            // named by its address, which tells unknown threads apart
+            None => Self {
+                tid: UNKNOWN_TID,
+                pid: UNKNOWN_TID,
+                name: addr.to_string(),
+                task: addr.to_string(),
+                state: None,
+                prio: None,
+            },
+        }
+    }
+}
+
+/// What the enricher derived from an event
+#[derive(Debug, Clone)]
+pub enum Detail {
+    /// `prev` got `preempted` if it didn't block
+    ContextSwitch {
+        prev: ThreadInfo,
+        next: ThreadInfo,
+        preempted: bool,
+    },
+    /// An IPC sent to `gate`, which is received by the thread `callee`
+    IpcMessage {
+        gate: String,
+        callee: String,
+        words: u64,
+        items: u64,
+    },
+    /// The IPC of an `IPCRES` event
+    IpcCompleted(CompletedIpc),
+    /// Send end of an IPC flow, the flow id is the number of the `IPC` event
+    IpcSend { flow_id: u64, receiver: ThreadInfo },
+    /// Receive end of an IPC flow
+    IpcReceive { flow_id: u64, sender: ThreadInfo },
+    /// The object created by a `FACTORY` event, `generation` counts the objects which lived at
+    /// its address before
+    ObjectCreated {
+        addr: KobjAddr,
+        kobj_type: KobjType,
+        generation: u32,
+    },
+    /// The object destroyed by a `DESTROY` event, `created` is the timestamp of its creation if
+    /// it was created while tracing
+    ObjectDestroyed {
+        addr: KobjAddr,
+        kobj_type: KobjType,
+        name: String,
+        generation: u32,
+        created: Option<u64>,
+    },
+    /// A scheduling context of the thread `tid` got saved, `expired` if no budget was left
+    SchedBudget {
+        tid: i64,
+        sc_id: u16,
+        prio: u16,
+        quantum: u64,
+        left: i64,
+        expired: bool,
+    },
+    /// The priority of a scheduling context changed between two `SCHED` events
+    SchedPrioChange {
+        tid: i64,
+        sc_id: u16,
+        old_prio: u16,
+        new_prio: u16,
+    },
+    /// The task of the thread became known, `pid` and `task` of the thread are set
+    TaskLinked(ThreadInfo),
+}
+
+/// The kernel object the context pointer of an event refers to, and the symbols of the
+/// instruction pointers of the event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventContext {
+    /// Name of the object, empty if it is unnamed or unknown
+    pub name: String,
+    /// `None` if the object is unknown
+    pub dbg_id: Option<DbgId>,
+    pub ip_sym: Option<String>,
+    /// Symbol of the user instruction pointer in the payload of `EXREGS`, `VCPU`, `MIGRATION`
+    /// and `TIMER` events
+    pub user_ip_sym: Option<String>,
+}
+
+/// An event with the state of the enricher after processing it
+#[derive(Debug, Clone)]
+pub struct SinkEvent {
+    pub event: Event,
+    pub cpu: u8,
+    /// Timestamp in clock ticks
+    pub tsc: u64,
+    /// The thread the event happened in
+    pub thread: ThreadInfo,
+    pub context: EventContext,
+    pub details: Vec<Detail>,
+}
+
+impl SinkEvent {
+    /// The payload fields of the event (without [`EventCommon`](crate::event::common::EventCommon)
+    /// and padding) in the order of the struct
+    pub fn payload(&self) -> Value {
+        let mut fields = serde_json::to_value(self.event).unwrap_or(Value::Null);
+        if let Some(map) = fields.as_object_mut() {
+            map.shift_remove("common");
+        }
+        fields
+    }
+}
+
+/// Receiver of the converted events, see the module documentation
+pub trait TraceSink {
+    fn event(&mut self, event: &SinkEvent);
+
+    /// Called with the number of events of `cpu` discarded by the sampler since the last event of
+    /// the CPU, see [`crate::sampling`]
+    fn discarded(&mut self, _cpu: u8, _count: u64) {}
+
+    /// Called with the context switches the filter or the sampler left out, with their
+    /// [`Detail::ContextSwitch`]. Sinks which follow the threads on the CPUs (CPU times, thread
+    /// states, slices) use them, so these don't depend on the filter and sampling rules.
+    fn left_out(&mut self, _event: &SinkEvent) {}
+
+    /// Called with the final kernel object model after the last event, before
+    /// [`finish`](Self::finish)
+    fn kernel_objects(&mut self, _objects: &KernelObjectMap) {}
+
+    /// Called after the last event
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
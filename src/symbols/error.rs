@@ -0,0 +1,10 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("IO error on reading an ELF file")]
+    Io(#[from] io::Error),
+    #[error("Could not parse ELF file")]
+    Object(#[from] object::Error),
+}
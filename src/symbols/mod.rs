@@ -0,0 +1,112 @@
+//! Resolution of instruction pointers to `function+offset` using the symbol tables of ELF
+//! binaries (`--symbols` and `--task-symbols`).
+//!
+//! Kernel addresses are resolved with the Fiasco ELF, user addresses with the ELF of the task the
+//! thread runs in, matched by the task name. User binaries are expected to be linked to the
+//! addresses they run at (static L4Re binaries), there is no load base adjustment.
+
+pub mod error;
+
+use error::Error;
+use log::info;
+use object::{Object, ObjectSymbol, SymbolKind};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// start of the upper half of the address space, where the kernel lives
+const KERNEL_BASE: u64 = 0xffff_8000_0000_0000;
+
+#[derive(Debug)]
+struct Symbol {
+    addr: u64,
+    size: u64,
+    name: String,
+}
+
+/// Function symbols of a single ELF, sorted by address
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        let file = object::File::parse(&*data)?;
+
+        let mut symbols: Vec<Symbol> = file
+            .symbols()
+            .filter(|s| s.kind() == SymbolKind::Text && s.address() != 0)
+            .filter_map(|s| {
+                Some(Symbol {
+                    addr: s.address(),
+                    size: s.size(),
+                    name: s.name().ok()?.to_string(),
+                })
+            })
+            .collect();
+        symbols.sort_by_key(|s| s.addr);
+        symbols.dedup_by_key(|s| s.addr);
+
+        info!("Loaded {} symbols from {:?}", symbols.len(), path);
+        Ok(Self { symbols })
+    }
+
+    /// The function containing `addr`, as `function+offset`. Symbols without a size are assumed
+    /// to reach up to the next symbol.
+    pub fn lookup(&self, addr: u64) -> Option<String> {
+        let idx = self
+            .symbols
+            .partition_point(|s| s.addr <= addr)
+            .checked_sub(1)?;
+        let sym = &self.symbols[idx];
+        let offset = addr - sym.addr;
+        if sym.size != 0 && offset >= sym.size {
+            return None;
+        }
+        Some(format!("{}+{:#x}", sym.name, offset))
+    }
+}
+
+/// Symbol tables of the kernel and of tasks
+#[derive(Debug, Default)]
+pub struct Symbolizer {
+    kernel: Option<SymbolTable>,
+    tasks: HashMap<String, SymbolTable>,
+}
+
+impl Symbolizer {
+    pub fn load(kernel: Option<&Path>, tasks: &[(String, PathBuf)]) -> Result<Self, Error> {
+        let kernel = kernel.map(SymbolTable::load).transpose()?;
+        let tasks = tasks
+            .iter()
+            .map(|(name, path)| Ok((name.clone(), SymbolTable::load(path)?)))
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self { kernel, tasks })
+    }
+
+    /// Resolves `addr`, which is a user address in the task named `task` if it isn't a kernel
+    /// address
+    pub fn resolve(&self, addr: u64, task: Option<&str>) -> Option<String> {
+        if addr == 0 {
+            return None;
+        }
+        if addr >= KERNEL_BASE {
+            self.kernel.as_ref()?.lookup(addr)
+        } else {
+            self.tasks.get(task?)?.lookup(addr)
+        }
+    }
+}
+
+/// Parses the `<task name>=<elf path>` argument of `--task-symbols`
+pub fn parse_task_symbols(arg: &str) -> Result<(String, PathBuf), String> {
+    match arg.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected <task name>=<elf path>, got '{arg}'")),
+    }
+}
@@ -0,0 +1,211 @@
+//! Golden output of the `EventParser`: the capture built here goes through the conversion without
+//! CTF and is dumped with `--format jsonl|text`, the dumps are compared with the files in
+//! `tests/golden/`. Run with `UPDATE_GOLDEN=1` to rewrite them after an intended change.
+
+use l4re_tracestream::conversion::{Conversion, Input, Summary};
+use l4re_tracestream::filter::EventFilter;
+use l4re_tracestream::sampling;
+use l4re_tracestream::sink::dump::{DumpFormat, EventDump};
+use std::cell::RefCell;
+use std::io::{self, Cursor, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::{env, fs};
+
+const CLOCK_FREQUENCY: u64 = 2_000_000_000;
+const EVENT_SIZE: usize = 128;
+
+// event types, see `src/event/event_type.rs`
+const PF: u8 = 1;
+const IPC: u8 = 2;
+const IPCRES: u8 = 3;
+const CONTEXTSWITCH: u8 = 9;
+const FACTORY: u8 = 17;
+const NAM: u8 = 21;
+
+const CLIENT: u64 = 0xffff_8000_0010_0000;
+const SERVER: u64 = 0xffff_8000_0020_0000;
+const TASK: u64 = 0xffff_8000_0030_0000;
+const GATE: u64 = 0xffff_8000_0040_0040;
+
+// a raw event, the common fields followed by the payload
+fn record(number: u64, type_: u8, cpu: u8, tsc: u64, ctx: u64, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(EVENT_SIZE);
+    buf.extend(number.to_le_bytes());
+    buf.extend(0xffff_ffff_f000_1000u64.wrapping_add(number).to_le_bytes()); // ip
+    buf.extend(tsc.to_le_bytes());
+    buf.extend(ctx.to_le_bytes());
+    buf.extend([0; 12]); // pmc1, pmc2, kclock
+    buf.push(type_);
+    buf.push(cpu);
+    buf.extend(payload);
+    buf.resize(EVENT_SIZE, 0);
+    buf
+}
+
+// the payload of the events starting with 2 bytes of padding
+fn words(words: &[u64]) -> Vec<u8> {
+    let mut buf = vec![0; 2];
+    for w in words {
+        buf.extend(w.to_le_bytes());
+    }
+    buf
+}
+
+fn nam(obj: u64, thread: u64, id: u64, name: &str) -> Vec<u8> {
+    let mut buf = words(&[obj, thread, id]);
+    let mut bytes = [0; 32];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    buf.extend(bytes);
+    buf
+}
+
+// `IPCRES` has `have_snd` and `is_np` instead of the padding
+fn ipc_res(fields: &[u64]) -> Vec<u8> {
+    let mut buf = words(fields);
+    buf[0] = 1;
+    buf
+}
+
+fn capture() -> Vec<u8> {
+    let events = [
+        record(1, NAM, 0, 1000, CLIENT, &nam(CLIENT, 0, 0x1a, "client")),
+        record(2, NAM, 0, 1100, CLIENT, &nam(SERVER, 0, 0x1b, "server")),
+        // task, op -11
+        record(
+            3,
+            FACTORY,
+            0,
+            1200,
+            CLIENT,
+            &words(&[(-11i64) as u64, 0, 0, 0, 0x1c, TASK]),
+        ),
+        record(4, NAM, 0, 1300, CLIENT, &nam(TASK, 0, 0x1c, "app")),
+        record(
+            5,
+            NAM,
+            0,
+            1400,
+            CLIENT,
+            &nam(GATE, SERVER, 0x1d, "srv_gate"),
+        ),
+        // dst, dst_orig, kernel_ip, lock_cnt, from_space, from_sched, from_prio
+        record(
+            6,
+            CONTEXTSWITCH,
+            0,
+            2000,
+            CLIENT,
+            &words(&[SERVER, SERVER, 0, 0, TASK, CLIENT, 100]),
+        ),
+        // pfa, error, space
+        record(7, PF, 0, 2500, SERVER, &words(&[0x1000, 0x6, TASK])),
+        // tag, dword, dst (call), dbg_id, label, timeout and padding, to_abs_rcv
+        record(
+            8,
+            IPC,
+            0,
+            3000,
+            CLIENT,
+            &words(&[0x2_0000, 1, 2, 0, 0x1d, 0, 0, 0]),
+        ),
+        // tag, dword, result, from, dst, pair_event
+        record(
+            9,
+            IPCRES,
+            0,
+            5000,
+            CLIENT,
+            &ipc_res(&[0x1_0000, 3, 4, 0, 0, 0, 8]),
+        ),
+        // duplicate, left out
+        record(9, PF, 1, 5100, SERVER, &words(&[0x2000, 0x4, TASK])),
+        // unknown type, not parsed
+        record(10, 200, 1, 5200, SERVER, &[]),
+        // the events 10 to 12 are missing
+        record(13, PF, 1, 6000, SERVER, &words(&[0x3000, 0x4, TASK])),
+    ];
+    events.concat()
+}
+
+// a writer to check the dump after the conversion
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn dump(format: DumpFormat) -> (String, Summary) {
+    let output = Output::default();
+    let dump = EventDump::new(format, Box::new(output.clone()), CLOCK_FREQUENCY);
+    let summary = Conversion::new(CLOCK_FREQUENCY)
+        .input(Input::Reader(Box::new(Cursor::new(capture()))))
+        .ctf(false)
+        .sink(Rc::new(RefCell::new(dump)))
+        .run()
+        .unwrap();
+    let dump = String::from_utf8(output.0.take()).unwrap();
+    (dump, summary)
+}
+
+fn check_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    assert_eq!(actual, expected, "dump differs from {}", path.display());
+}
+
+#[test]
+fn jsonl_dump() {
+    let (dump, summary) = dump(DumpFormat::Jsonl);
+    check_golden("events.jsonl", &dump);
+
+    assert_eq!(summary.events, 10);
+    assert_eq!(summary.dropped_events, 3);
+    assert_eq!(summary.cpus, vec![0, 1]);
+}
+
+#[test]
+fn text_dump() {
+    let (dump, _) = dump(DumpFormat::Text);
+    check_golden("events.txt", &dump);
+}
+
+#[test]
+fn left_out_events_not_counted() {
+    let conversion = || {
+        Conversion::new(CLOCK_FREQUENCY)
+            .input(Input::Reader(Box::new(Cursor::new(capture()))))
+            .ctf(false)
+    };
+
+    let summary = conversion()
+        .filter(EventFilter::new(
+            vec!["type=pf".parse().unwrap()],
+            Vec::new(),
+        ))
+        .run()
+        .unwrap();
+    assert_eq!(summary.events, 2);
+    assert_eq!(summary.filtered_events, 8);
+
+    // the NAM events 1 and 4 are kept
+    let summary = conversion()
+        .sampling(vec![sampling::parse_sample("nam=2").unwrap()])
+        .run()
+        .unwrap();
+    assert_eq!(summary.events, 8);
+    assert_eq!(summary.discarded_events.values().sum::<u64>(), 2);
+}
@@ -202,9 +202,11 @@ class Fiasco_tbuf(gdb.Command):
                     if self.base_block_size != 0 and byteoff != self.base_block_size:
                         # Add padding
                         padding = byteoff - self.base_block_size
+                        self.printlogi(indent, "#[serde(skip)]\n")
                         self.printlogi(indent, "pub __pre_pad: [i8; %d],\n" % padding)
                 elif cur_size < byteoff:
                     padding = byteoff - cur_size
+                    self.printlogi(indent, "#[serde(skip)]\n")
                     self.printlogi(
                         indent, "pub __pad_%d: [i8; %d],\n" % (padidx, padding)
                     )
@@ -213,6 +215,12 @@ class Fiasco_tbuf(gdb.Command):
                 behind_last_member = byteoff + f.type.sizeof
                 if f.type.code == gdb.TYPE_CODE_ARRAY:
                     tc = self.handle_type(f.type.target().unqualified())
+                    if tc == "i8":
+                        # char arrays are (NUL terminated) strings
+                        self.printlogi(
+                            indent,
+                            '#[serde(serialize_with = "crate::helpers::serialize_c_string")]\n',
+                        )
                     c = "pub %s: [%s; %d]" % (
                         f.name.removeprefix("_"),
                         tc,
@@ -313,7 +321,8 @@ class Fiasco_tbuf(gdb.Command):
         # Print struct for common event
         self.printlog("/* Note, automatically generated from Fiasco binary */\n")
         self.printlog("\n")
-        self.printlog("use binrw::BinRead;\n\n")
+        self.printlog("use binrw::BinRead;\n")
+        self.printlog("use serde::Serialize;\n\n")
         self.print_derive_traits()
         self.printlog("#[br(little)]\n")
         self.printlog("pub struct EventCommon {\n")
@@ -338,6 +347,7 @@ class Fiasco_tbuf(gdb.Command):
                 self.printlog("use ctf_macros::CtfEventClass;\n\n")
                 self.printlog("use super::common::EventCommon;\n")
                 self.printlog("use binrw::BinRead;\n")
+                self.printlog("use serde::Serialize;\n")
                 self.print_single_struct(i, self.to_camel_case(name))
                 self.printlog("\n")
                 self.printlog_write(name + ".rs")
@@ -380,12 +390,12 @@ class Fiasco_tbuf(gdb.Command):
     def print_derive_traits(self, name=""):
         if name != "" and name not in self.no_bt_impl:
             self.printlog(
-                "#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, CtfEventClass)]\n"
+                "#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, CtfEventClass)]\n"
             )
             self.printlog('#[event_name = "%s"]\n' % name.upper())
         else:
             self.printlog(
-                "#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]\n"
+                "#[derive(BinRead, Copy, Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize)]\n"
             )
 
     def gen_event_type(self):